@@ -0,0 +1,18 @@
+use std::process::Command;
+
+// Sets GIT_COMMIT for the parser version recorded with dead letters
+fn main() {
+    let commit = Command::new("git")
+        .args(&["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .and_then(|x| String::from_utf8(x.stdout).ok())
+        .map(|x| x.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=GIT_COMMIT={}", commit);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-changed=src");
+}
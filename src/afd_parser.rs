@@ -1,11 +1,10 @@
 use super::domain::Product;
+use super::parser::{get_issuance_ticks, FieldError};
 use wx::domain::{Event, EventType};
-use wx::error::Error;
-use wx::util;
 
-pub fn parse(product: &Product) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product) -> Result<Option<Event>, FieldError> {
     let wfo = product.issuing_office.to_string();
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
+    let event_ts = get_issuance_ticks(product)?;
     let title = format!("Area Forecast Discussion ({})", wfo);
     let ext_uri = Some(product._id.to_string());
 
@@ -0,0 +1,247 @@
+use super::config::Config;
+use super::dead_letter::DeadLetters;
+use super::domain::{ListProduct, Product, ProductsResult};
+use super::hazards::Hazards;
+use super::lsr_parser::ReportLog;
+use super::pipeline::Pipeline;
+use super::registry::Registry;
+use super::sink::Sinks;
+use super::util::Fetcher;
+use slog::Logger;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use wx::error::{Error, WxError};
+use wx::util;
+
+pub const BACKFILL_COMMAND: &str = "backfill";
+const USAGE: &str = "backfill <product code> <start> <end> [wfo], ex. backfill tor 2019-04-22T00:00:00+00:00 2019-04-23T00:00:00+00:00 MAF";
+
+/**
+ * Loads products issued between two times, rather than only looking forward from startup. Used
+ * to seed a fresh store, ex. with the last outbreak.
+ */
+#[derive(Debug, PartialEq)]
+pub struct Backfill {
+    pub product_code: String,
+    pub start_ts: u64,
+    pub end_ts: u64,
+    pub wfo: Option<String>,
+}
+
+/**
+ * What a backfill stored, and the IDs of any listed products it couldn't fetch. Those are
+ * gaps in the range, so they're reported rather than only logged.
+ */
+#[derive(Debug, Default, PartialEq)]
+pub struct BackfillSummary {
+    pub stored: usize,
+    pub failed_ids: Vec<String>,
+}
+
+impl Backfill {
+    /**
+     * Reads the arguments following the `backfill` command. Times use the same format as the
+     * API's `issuanceTime`, and the range includes both ends.
+     */
+    pub fn from_args(args: &[String], registry: &Registry) -> Result<Backfill, Error> {
+        if args.len() < 3 || args.len() > 4 {
+            return Err(get_backfill_error(USAGE));
+        }
+
+        let product_code = args[0].to_lowercase();
+        if registry.get(&product_code).is_none() {
+            return Err(get_backfill_error(&format!(
+                "unsupported product code: {}",
+                args[0]
+            )));
+        }
+
+        let start_ts = get_ticks(&args[1])?;
+        let end_ts = get_ticks(&args[2])?;
+        if start_ts > end_ts {
+            return Err(get_backfill_error("start must not be after end"));
+        }
+
+        Ok(Backfill {
+            product_code,
+            start_ts,
+            end_ts,
+            wfo: args.get(3).map(|x| x.to_uppercase()),
+        })
+    }
+
+    pub fn get_url(&self, api_host: &str) -> String {
+        let url = format!("{}/products/types/{}", api_host, self.product_code);
+        match &self.wfo {
+            Some(wfo) => format!("{}/locations/{}", url, wfo),
+            None => url,
+        }
+    }
+
+    /**
+     * Pages through the product list until it's past the start of the range, then stores the
+     * events from every product in it, oldest first. Returns the number of events stored and
+     * the products that couldn't be fetched.
+     */
+    pub fn run(
+        &self,
+        config: &Config,
+        registry: Arc<Registry>,
+        logger: &Logger,
+    ) -> Result<BackfillSummary, Error> {
+        let fetcher = Fetcher::new(logger, &config.user_agent, &config.http)?;
+        let sinks = Arc::new(Sinks::from_config(&config.sinks, &config.spool)?);
+        let dead_letters = DeadLetters::new(&config.dead_letter_dir);
+        let hazards = Arc::new(Mutex::new(Hazards::new()));
+        // Shared with polling, so reports it already stored aren't stored again
+        let report_log = Arc::new(Mutex::new(ReportLog::load(&config.report_log_path)?));
+        let mut pipeline =
+            Pipeline::new(logger, registry, hazards, report_log, sinks, dead_letters);
+        let mut products: Vec<(u64, ListProduct)> = vec![];
+        let mut ids = HashSet::new();
+        let mut url = Some(self.get_url(&config.api_host));
+
+        while let Some(page_url) = url {
+            let page = fetcher.fetch::<ProductsResult>(&page_url)?;
+            let next_url = page.pagination.as_ref().map(|x| x.next.to_string());
+            let (in_range, is_complete) = get_products_in_range(self.start_ts, self.end_ts, page);
+            info!(logger, "backfill page"; "url" => &page_url, "products" => in_range.len());
+
+            // Pages can overlap if products are issued while paging
+            products.extend(
+                in_range
+                    .into_iter()
+                    .filter(|x| ids.insert(x.1.id.to_string())),
+            );
+            url = if is_complete { None } else { next_url };
+        }
+
+        products.sort_by_key(|x| x.0);
+        let mut summary = BackfillSummary::default();
+
+        for (_, list_product) in products {
+            let product = match fetcher.fetch_cached::<Product>(&list_product._id) {
+                Ok(value) => value,
+                Err(error) => {
+                    error!(logger, "Fetch error"; "id" => &list_product.id, "error" => format!("{}", error));
+                    summary.failed_ids.push(list_product.id);
+                    continue;
+                }
+            };
+
+            summary.stored += pipeline.process(&product);
+        }
+
+        Ok(summary)
+    }
+}
+
+/**
+ * Returns the products in a page that fall within the range, along with whether paging can
+ * stop. Pages are listed newest first, but ordering isn't relied on, so paging only stops at a
+ * page where every product is from before the start.
+ */
+fn get_products_in_range(
+    start_ts: u64,
+    end_ts: u64,
+    page: ProductsResult,
+) -> (Vec<(u64, ListProduct)>, bool) {
+    let mut is_complete = true;
+    let mut products = vec![];
+
+    for product in page.products {
+        if let Ok(ticks) = util::ts_to_ticks(&product.issuance_time) {
+            if ticks >= start_ts {
+                is_complete = false;
+                if ticks <= end_ts {
+                    products.push((ticks, product));
+                }
+            }
+        }
+    }
+
+    (products, is_complete)
+}
+
+fn get_ticks(input: &str) -> Result<u64, Error> {
+    util::ts_to_ticks(input).map_err(|_| get_backfill_error(&format!("invalid time: {}", input)))
+}
+
+fn get_backfill_error(reason: &str) -> Error {
+    let reason = format!("invalid backfill: {}", reason);
+    Error::Wx(<WxError>::new(&reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn get_args(input: &str) -> Vec<String> {
+        input.split(' ').map(ToString::to_string).collect()
+    }
+
+    fn get_product_list() -> ProductsResult {
+        let contents = fs::read_to_string("data/product-list-tor").unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    #[test]
+    fn from_args_should_read_the_range_and_office() {
+        let args = get_args("TOR 2019-04-22T23:51:00+00:00 2019-04-22T23:52:00+00:00 maf");
+        let result = Backfill::from_args(&args, &Registry::new()).unwrap();
+        let expected = Backfill {
+            product_code: "tor".to_string(),
+            start_ts: 1555977060000000,
+            end_ts: 1555977120000000,
+            wfo: Some("MAF".to_string()),
+        };
+        assert_eq!(expected, result);
+        assert_eq!(
+            "https://api.weather.gov/products/types/tor/locations/MAF",
+            result.get_url("https://api.weather.gov")
+        );
+    }
+
+    #[test]
+    fn from_args_reversed_range_should_be_an_error() {
+        let args = get_args("tor 2019-04-22T23:52:00+00:00 2019-04-22T23:51:00+00:00");
+        assert!(Backfill::from_args(&args, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn from_args_unsupported_product_should_be_an_error() {
+        let args = get_args("xyz 2019-04-22T23:51:00+00:00 2019-04-22T23:52:00+00:00");
+        assert!(Backfill::from_args(&args, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn get_products_in_range_should_include_both_ends() {
+        let (products, is_complete) =
+            get_products_in_range(1555977060000000, 1555977120000000, get_product_list());
+        let ids: Vec<&str> = products.iter().map(|x| x.1.id.as_str()).collect();
+        let expected_ids = vec![
+            "e0fdf7de-6229-4330-9d4d-3a2af96ffa4d",
+            "e0fdf7de-6229-4330-9d4d-3a2af96ffa4e",
+        ];
+        assert_eq!(expected_ids, ids);
+        // The page also has newer products, so older ones could be on the next page
+        assert!(!is_complete);
+    }
+
+    #[test]
+    fn get_products_in_range_with_only_older_products_should_be_complete() {
+        let (products, is_complete) =
+            get_products_in_range(1555977240000000, 1555977300000000, get_product_list());
+        assert!(products.is_empty());
+        assert!(is_complete);
+    }
+
+    #[test]
+    fn get_products_in_range_before_the_start_should_be_incomplete() {
+        let (products, is_complete) =
+            get_products_in_range(1555900000000000, 1555977120000000, get_product_list());
+        assert_eq!(3, products.len());
+        assert!(!is_complete);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use wx::error::Error;
+
+// Enough to cover a full product listing from the API
+const MAX_SEEN_IDS: usize = 500;
+
+/**
+ * Where a product code's worker left off: the issuance time of the newest product it handled,
+ * and the IDs of recently handled products.
+ */
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+    pub last_product_ts: u64,
+    pub seen_ids: Vec<String>,
+}
+
+impl Checkpoint {
+    pub fn has_seen(&self, id: &str) -> bool {
+        self.seen_ids.iter().any(|x| x == id)
+    }
+
+    /**
+     * Remembers a handled product, forgetting the oldest IDs once there are too many. The
+     * timestamp only moves forward, so handling a late product doesn't rewind it.
+     */
+    pub fn mark_seen(&mut self, id: &str, product_ts: u64) {
+        self.last_product_ts = self.last_product_ts.max(product_ts);
+
+        if self.has_seen(id) {
+            return;
+        }
+
+        self.seen_ids.push(id.to_string());
+        if self.seen_ids.len() > MAX_SEEN_IDS {
+            let overflow = self.seen_ids.len() - MAX_SEEN_IDS;
+            self.seen_ids.drain(..overflow);
+        }
+    }
+}
+
+/**
+ * Checkpoints for every product code, persisted as a single JSON file so workers can resume
+ * after a restart instead of skipping whatever was issued while the loader was down.
+ */
+pub struct CheckpointStore {
+    path: String,
+    checkpoints: HashMap<String, Checkpoint>,
+}
+
+impl CheckpointStore {
+    /**
+     * Reads the checkpoint file, starting empty if it doesn't exist yet.
+     */
+    pub fn load(path: &str) -> Result<CheckpointStore, Error> {
+        let checkpoints = if Path::new(path).exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(CheckpointStore {
+            path: path.to_string(),
+            checkpoints,
+        })
+    }
+
+    pub fn get(&self, product_code: &str) -> Option<Checkpoint> {
+        self.checkpoints.get(product_code).cloned()
+    }
+
+    /**
+     * Updates a product code's checkpoint and rewrites the file. The new contents are written
+     * to a temporary file first, so a crash mid-write can't corrupt the existing checkpoints.
+     */
+    pub fn save(&mut self, product_code: &str, checkpoint: &Checkpoint) -> Result<(), Error> {
+        self.checkpoints
+            .insert(product_code.to_string(), checkpoint.clone());
+        let tmp_path = format!("{}.tmp", self.path);
+        fs::write(&tmp_path, serde_json::to_string(&self.checkpoints)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/**
+ * Returns the timestamp a worker should resume polling from. Without a checkpoint it starts
+ * from now, and an old checkpoint only catches up as far back as the window allows.
+ */
+pub fn get_resume_ts(checkpoint: Option<&Checkpoint>, now: u64, max_catch_up_ms: u64) -> u64 {
+    match checkpoint {
+        Some(value) => {
+            let earliest_ts = now.saturating_sub(max_catch_up_ms * 1000);
+            value.last_product_ts.max(earliest_ts)
+        }
+        None => now,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const NOW: u64 = 1_555_977_060_000_000;
+    const HOUR_MS: u64 = 60 * 60 * 1000;
+
+    #[test]
+    fn get_resume_ts_without_checkpoint_should_be_now() {
+        assert_eq!(NOW, get_resume_ts(None, NOW, HOUR_MS));
+    }
+
+    #[test]
+    fn get_resume_ts_should_resume_recent_checkpoints() {
+        let checkpoint = Checkpoint {
+            last_product_ts: NOW - 10 * 60 * 1_000_000,
+            seen_ids: vec![],
+        };
+        let result = get_resume_ts(Some(&checkpoint), NOW, HOUR_MS);
+        assert_eq!(checkpoint.last_product_ts, result);
+    }
+
+    #[test]
+    fn get_resume_ts_should_limit_catch_up() {
+        let checkpoint = Checkpoint {
+            last_product_ts: NOW - 5 * HOUR_MS * 1000,
+            seen_ids: vec![],
+        };
+        let result = get_resume_ts(Some(&checkpoint), NOW, HOUR_MS);
+        assert_eq!(NOW - HOUR_MS * 1000, result);
+    }
+
+    #[test]
+    fn mark_seen_should_forget_the_oldest_ids() {
+        let mut checkpoint = Checkpoint::default();
+        for i in 0..=MAX_SEEN_IDS {
+            checkpoint.mark_seen(&i.to_string(), NOW);
+        }
+        checkpoint.mark_seen("1", NOW);
+        assert_eq!(MAX_SEEN_IDS, checkpoint.seen_ids.len());
+        assert!(!checkpoint.has_seen("0"));
+        assert!(checkpoint.has_seen("1"));
+    }
+
+    #[test]
+    fn mark_seen_should_not_rewind_the_timestamp() {
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.mark_seen("a", NOW);
+        checkpoint.mark_seen("b", NOW - 1);
+        assert_eq!(NOW, checkpoint.last_product_ts);
+        assert!(checkpoint.has_seen("b"));
+    }
+
+    #[test]
+    fn save_should_be_reloaded() {
+        let path = env::temp_dir().join("nws-api-loader-checkpoints.json");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let mut checkpoint = Checkpoint::default();
+        checkpoint.mark_seen("e0fdf7de-6229-4330-9d4d-3a2af96ffa4c", NOW);
+
+        let mut store = CheckpointStore::load(path).unwrap();
+        assert_eq!(None, store.get("tor"));
+        store.save("tor", &checkpoint).unwrap();
+
+        let store = CheckpointStore::load(path).unwrap();
+        assert_eq!(Some(checkpoint), store.get("tor"));
+        let _ = fs::remove_file(path);
+    }
+}
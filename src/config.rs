@@ -0,0 +1,537 @@
+use super::registry::Registry;
+use slog::Level;
+use std::fs;
+use wx::error::{Error, WxError};
+
+const DEFAULT_API_HOST: &str = "https://api.weather.gov";
+const DEFAULT_CHECKPOINT_PATH: &str = "checkpoints.json";
+const DEFAULT_DEAD_LETTER_DIR: &str = "dead-letters";
+const DEFAULT_HAZARDS_PATH: &str = "hazards.json";
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_MAX_CATCH_UP_MS: u64 = 6 * 60 * 60 * 1000;
+const DEFAULT_MAX_FILES: usize = 5;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 60_000;
+const DEFAULT_REPORT_LOG_PATH: &str = "reports.json";
+const DEFAULT_WEBHOOK_TIMEOUT_MS: u64 = 5_000;
+
+/**
+ * Loader settings, read from a TOML file at startup. Each product gets its own worker, polling
+ * at its own interval, the top-level one, or its parser's cadence.
+ */
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(default = "default_api_host")]
+    pub api_host: String,
+    pub user_agent: String,
+    #[serde(default = "default_checkpoint_path")]
+    pub checkpoint_path: String,
+    #[serde(default = "default_dead_letter_dir")]
+    pub dead_letter_dir: String,
+    #[serde(default = "default_hazards_path")]
+    pub hazards_path: String,
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    #[serde(default = "default_max_catch_up_ms")]
+    pub max_catch_up_ms: u64,
+    #[serde(default)]
+    pub poll_interval_ms: Option<u64>,
+    #[serde(default = "default_report_log_path")]
+    pub report_log_path: String,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default = "default_sinks")]
+    pub sinks: Vec<SinkConfig>,
+    #[serde(default)]
+    pub spool: SpoolConfig,
+    pub products: Vec<ProductConfig>,
+}
+
+/**
+ * Where events a sink rejects are kept until it takes them, one file per sink, and how often
+ * to retry them. Retries back off exponentially from `retry_base_ms` up to `retry_max_ms`.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SpoolConfig {
+    pub dir: String,
+    pub retry_base_ms: u64,
+    pub retry_max_ms: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> SpoolConfig {
+        SpoolConfig {
+            dir: "spool".to_string(),
+            retry_base_ms: 1_000,
+            retry_max_ms: 300_000,
+        }
+    }
+}
+
+/**
+ * Where events are sent. Every event goes to each of them.
+ */
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SinkConfig {
+    Store,
+    Stdout,
+    File {
+        path: String,
+        #[serde(default = "default_max_bytes")]
+        max_bytes: u64,
+        #[serde(default = "default_max_files")]
+        max_files: usize,
+    },
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+/**
+ * How requests to the API are made. Retries back off exponentially from `backoff_base_ms` up
+ * to `backoff_max_ms`, unless the API says how long to wait with `Retry-After` (which is also
+ * held to `backoff_max_ms`). Requests to the same host are spaced at least
+ * `min_request_interval_ms` apart across every worker.
+ */
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub connect_timeout_ms: u64,
+    pub request_timeout_ms: u64,
+    pub max_retries: u32,
+    pub backoff_base_ms: u64,
+    pub backoff_max_ms: u64,
+    pub min_request_interval_ms: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> HttpConfig {
+        HttpConfig {
+            connect_timeout_ms: 5_000,
+            request_timeout_ms: 15_000,
+            max_retries: 3,
+            backoff_base_ms: 500,
+            backoff_max_ms: 30_000,
+            min_request_interval_ms: 200,
+        }
+    }
+}
+
+impl SinkConfig {
+    /**
+     * Names the file a sink's rejected events are spooled to, by its type and target, so a
+     * spool is only ever replayed into the sink it came from.
+     */
+    pub fn get_spool_name(&self) -> String {
+        let (name, target) = match self {
+            SinkConfig::Store => ("store", None),
+            SinkConfig::Stdout => ("stdout", None),
+            SinkConfig::File { path, .. } => ("file", Some(path)),
+            SinkConfig::Webhook { url, .. } => ("webhook", Some(url)),
+        };
+
+        match target {
+            Some(target) => {
+                let target: String = target
+                    .chars()
+                    .map(|x| if x.is_ascii_alphanumeric() { x } else { '_' })
+                    .collect();
+                format!("{}-{}.jsonl", name, target)
+            }
+            None => format!("{}.jsonl", name),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProductConfig {
+    pub code: String,
+    pub poll_interval_ms: Option<u64>,
+}
+
+impl Config {
+    pub fn load(path: &str, registry: &Registry) -> Result<Config, Error> {
+        let contents = fs::read_to_string(path)?;
+        Config::parse(&contents, registry)
+    }
+
+    pub fn parse(contents: &str, registry: &Registry) -> Result<Config, Error> {
+        let config: Config =
+            toml::from_str(contents).map_err(|e| get_config_error(&e.to_string()))?;
+        config.validate(registry)?;
+        Ok(config)
+    }
+
+    pub fn get_log_level(&self) -> Level {
+        get_level(&self.log_level).unwrap_or(Level::Info)
+    }
+
+    /**
+     * A product's own poll_interval_ms wins, then the top-level one, then its parser's cadence,
+     * then the default. Parser cadences only apply where the config doesn't set an interval.
+     */
+    pub fn get_poll_interval_ms(&self, product: &ProductConfig, registry: &Registry) -> u64 {
+        product
+            .poll_interval_ms
+            .or(self.poll_interval_ms)
+            .or_else(|| {
+                registry
+                    .get(&product.code)
+                    .and_then(|x| x.poll_interval_ms())
+            })
+            .unwrap_or(DEFAULT_POLL_INTERVAL_MS)
+    }
+
+    fn validate(&self, registry: &Registry) -> Result<(), Error> {
+        if !self.api_host.starts_with("http://") && !self.api_host.starts_with("https://") {
+            return Err(get_config_error(&format!(
+                "api_host must be an http(s) URL: {}",
+                self.api_host
+            )));
+        }
+
+        // api.weather.gov rejects requests without a way to contact the caller
+        if self.user_agent.trim().is_empty() {
+            return Err(get_config_error("user_agent must not be empty"));
+        }
+
+        if self.checkpoint_path.trim().is_empty() {
+            return Err(get_config_error("checkpoint_path must not be empty"));
+        }
+
+        if self.hazards_path.trim().is_empty() {
+            return Err(get_config_error("hazards_path must not be empty"));
+        }
+
+        if self.report_log_path.trim().is_empty() {
+            return Err(get_config_error("report_log_path must not be empty"));
+        }
+
+        if get_level(&self.log_level).is_none() {
+            return Err(get_config_error(&format!(
+                "unknown log_level: {}",
+                self.log_level
+            )));
+        }
+
+        if self.http.connect_timeout_ms == 0 || self.http.request_timeout_ms == 0 {
+            return Err(get_config_error("http timeouts must be positive"));
+        }
+
+        if self.http.backoff_base_ms > self.http.backoff_max_ms {
+            return Err(get_config_error(
+                "http backoff_base_ms must not be more than backoff_max_ms",
+            ));
+        }
+
+        if self.sinks.is_empty() {
+            return Err(get_config_error("at least one sink is required"));
+        }
+
+        for (i, sink) in self.sinks.iter().enumerate() {
+            let spool_name = sink.get_spool_name();
+            if self.sinks[..i]
+                .iter()
+                .any(|x| x.get_spool_name() == spool_name)
+            {
+                return Err(get_config_error(&format!(
+                    "sinks must not share a type and target: {}",
+                    spool_name
+                )));
+            }
+
+            match sink {
+                SinkConfig::File {
+                    path, max_bytes, ..
+                } if path.trim().is_empty() || *max_bytes == 0 => {
+                    return Err(get_config_error(
+                        "file sinks need a path and a positive max_bytes",
+                    ));
+                }
+                SinkConfig::Webhook { url, .. }
+                    if !url.starts_with("http://") && !url.starts_with("https://") =>
+                {
+                    return Err(get_config_error(&format!(
+                        "webhook url must be an http(s) URL: {}",
+                        url
+                    )));
+                }
+                _ => (),
+            }
+        }
+
+        if self.dead_letter_dir.trim().is_empty() {
+            return Err(get_config_error("dead_letter_dir must not be empty"));
+        }
+
+        if self.spool.dir.trim().is_empty() {
+            return Err(get_config_error("spool dir must not be empty"));
+        }
+
+        if self.spool.retry_base_ms > self.spool.retry_max_ms {
+            return Err(get_config_error(
+                "spool retry_base_ms must not be more than retry_max_ms",
+            ));
+        }
+
+        if self.products.is_empty() {
+            return Err(get_config_error("at least one product is required"));
+        }
+
+        let mut codes = vec![];
+        for product in &self.products {
+            let code = product.code.to_uppercase();
+
+            if registry.get(&code).is_none() {
+                return Err(get_config_error(&format!(
+                    "unsupported product code: {} (supported: {})",
+                    product.code,
+                    registry.codes().join(", ")
+                )));
+            }
+
+            if codes.contains(&code) {
+                return Err(get_config_error(&format!(
+                    "duplicate product code: {}",
+                    product.code
+                )));
+            }
+
+            if self.get_poll_interval_ms(product, registry) == 0 {
+                return Err(get_config_error(&format!(
+                    "poll_interval_ms must be positive for product: {}",
+                    product.code
+                )));
+            }
+
+            codes.push(code);
+        }
+
+        Ok(())
+    }
+}
+
+fn get_level(input: &str) -> Option<Level> {
+    match input.to_lowercase().as_str() {
+        "critical" => Some(Level::Critical),
+        "error" => Some(Level::Error),
+        "warning" | "warn" => Some(Level::Warning),
+        "info" => Some(Level::Info),
+        "debug" => Some(Level::Debug),
+        "trace" => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+fn get_config_error(reason: &str) -> Error {
+    let reason = format!("invalid config: {}", reason);
+    Error::Wx(<WxError>::new(&reason))
+}
+
+fn default_api_host() -> String {
+    DEFAULT_API_HOST.to_string()
+}
+
+fn default_checkpoint_path() -> String {
+    DEFAULT_CHECKPOINT_PATH.to_string()
+}
+
+fn default_dead_letter_dir() -> String {
+    DEFAULT_DEAD_LETTER_DIR.to_string()
+}
+
+fn default_hazards_path() -> String {
+    DEFAULT_HAZARDS_PATH.to_string()
+}
+
+fn default_log_level() -> String {
+    DEFAULT_LOG_LEVEL.to_string()
+}
+
+fn default_max_catch_up_ms() -> u64 {
+    DEFAULT_MAX_CATCH_UP_MS
+}
+
+fn default_report_log_path() -> String {
+    DEFAULT_REPORT_LOG_PATH.to_string()
+}
+
+fn default_sinks() -> Vec<SinkConfig> {
+    vec![SinkConfig::Store]
+}
+
+fn default_max_bytes() -> u64 {
+    DEFAULT_MAX_BYTES
+}
+
+fn default_max_files() -> usize {
+    DEFAULT_MAX_FILES
+}
+
+fn default_webhook_timeout_ms() -> u64 {
+    DEFAULT_WEBHOOK_TIMEOUT_MS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_apply_defaults() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[[products]]\ncode = \"afd\"\n";
+        let registry = Registry::new();
+        let config = Config::parse(contents, &registry).unwrap();
+        assert_eq!("https://api.weather.gov", config.api_host);
+        assert_eq!("checkpoints.json", config.checkpoint_path);
+        assert_eq!("hazards.json", config.hazards_path);
+        assert_eq!("reports.json", config.report_log_path);
+        assert_eq!(21_600_000, config.max_catch_up_ms);
+        assert_eq!(Level::Info, config.get_log_level());
+        assert_eq!(
+            60_000,
+            config.get_poll_interval_ms(&config.products[0], &registry)
+        );
+        assert_eq!(3, config.http.max_retries);
+        assert_eq!(vec![SinkConfig::Store], config.sinks);
+        assert_eq!("spool", config.spool.dir);
+        assert_eq!("dead-letters", config.dead_letter_dir);
+    }
+
+    #[test]
+    fn get_spool_name_should_include_the_target() {
+        let webhook = SinkConfig::Webhook {
+            url: "http://localhost:8080/events".to_string(),
+            timeout_ms: 5_000,
+        };
+        assert_eq!(
+            "webhook-http___localhost_8080_events.jsonl",
+            webhook.get_spool_name()
+        );
+        assert_eq!("store.jsonl", SinkConfig::Store.get_spool_name());
+    }
+
+    #[test]
+    fn parse_duplicate_sinks_should_fail() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[[sinks]]\ntype = \"store\"\n\n[[sinks]]\ntype = \"store\"\n\n[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_should_read_sinks() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[[sinks]]\ntype = \"stdout\"\n\n[[sinks]]\ntype = \"file\"\npath = \"events.jsonl\"\nmax_files = 2\n\n[[sinks]]\ntype = \"webhook\"\nurl = \"http://localhost:8080/events\"\n\n[[products]]\ncode = \"tor\"\n";
+        let config = Config::parse(contents, &Registry::new()).unwrap();
+        let expected = vec![
+            SinkConfig::Stdout,
+            SinkConfig::File {
+                path: "events.jsonl".to_string(),
+                max_bytes: 100 * 1024 * 1024,
+                max_files: 2,
+            },
+            SinkConfig::Webhook {
+                url: "http://localhost:8080/events".to_string(),
+                timeout_ms: 5_000,
+            },
+        ];
+        assert_eq!(expected, config.sinks);
+    }
+
+    #[test]
+    fn parse_unknown_sink_should_be_an_error() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[[sinks]]\ntype = \"kafka\"\n\n[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_should_read_http_settings() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[http]\nmax_retries = 5\nrequest_timeout_ms = 2000\n\n[[products]]\ncode = \"tor\"\n";
+        let config = Config::parse(contents, &Registry::new()).unwrap();
+        assert_eq!(5, config.http.max_retries);
+        assert_eq!(2_000, config.http.request_timeout_ms);
+        assert_eq!(5_000, config.http.connect_timeout_ms);
+    }
+
+    #[test]
+    fn parse_backoff_base_over_max_should_be_an_error() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[http]\nbackoff_base_ms = 60000\n\n[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_should_read_per_product_intervals() {
+        let contents = "user_agent = \"sigtor.org\"\nlog_level = \"debug\"\n\n[[products]]\ncode = \"tor\"\npoll_interval_ms = 10000\n\n[[products]]\ncode = \"svr\"\n\n[[products]]\ncode = \"afd\"\n";
+        let registry = Registry::new();
+        let config = Config::parse(contents, &registry).unwrap();
+        assert_eq!(Level::Debug, config.get_log_level());
+        assert_eq!(
+            10_000,
+            config.get_poll_interval_ms(&config.products[0], &registry)
+        );
+        // SVR's parser polls faster than the default
+        assert_eq!(
+            15_000,
+            config.get_poll_interval_ms(&config.products[1], &registry)
+        );
+        assert_eq!(
+            60_000,
+            config.get_poll_interval_ms(&config.products[2], &registry)
+        );
+    }
+
+    #[test]
+    fn parse_top_level_interval_should_win_over_parser_cadence() {
+        let contents = "user_agent = \"sigtor.org\"\npoll_interval_ms = 30000\n\n[[products]]\ncode = \"tor\"\npoll_interval_ms = 10000\n\n[[products]]\ncode = \"svr\"\n";
+        let registry = Registry::new();
+        let config = Config::parse(contents, &registry).unwrap();
+        assert_eq!(
+            10_000,
+            config.get_poll_interval_ms(&config.products[0], &registry)
+        );
+        assert_eq!(
+            30_000,
+            config.get_poll_interval_ms(&config.products[1], &registry)
+        );
+    }
+
+    #[test]
+    fn parse_unsupported_product_should_be_an_error() {
+        let contents = "user_agent = \"sigtor.org\"\n\n[[products]]\ncode = \"xyz\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_duplicate_product_should_be_an_error() {
+        let contents =
+            "user_agent = \"sigtor.org\"\n\n[[products]]\ncode = \"tor\"\n\n[[products]]\ncode = \"TOR\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_zero_interval_should_be_an_error() {
+        let contents =
+            "user_agent = \"sigtor.org\"\npoll_interval_ms = 0\n\n[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_bad_log_level_should_be_an_error() {
+        let contents =
+            "user_agent = \"sigtor.org\"\nlog_level = \"loud\"\n\n[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn parse_missing_user_agent_should_be_an_error() {
+        let contents = "[[products]]\ncode = \"tor\"\n";
+        assert!(Config::parse(contents, &Registry::new()).is_err());
+    }
+
+    #[test]
+    fn load_sample_config() {
+        let config = Config::load("config.toml", &Registry::new()).unwrap();
+        assert_eq!(12, config.products.len());
+    }
+}
@@ -0,0 +1,216 @@
+use super::config::Config;
+use super::domain::Product;
+use super::hazards::Hazards;
+use super::lsr_parser::ReportLog;
+use super::parser::ParseError;
+use super::pipeline::Pipeline;
+use super::registry::Registry;
+use super::sink::Sinks;
+use slog::Logger;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use wx::error::Error;
+
+pub const REPROCESS_COMMAND: &str = "reprocess";
+// Recorded with each dead letter, so it's clear which fixes it predates. The commit is set by
+// build.rs, and changes with every parser fix where the package version doesn't.
+pub const PARSER_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("GIT_COMMIT"));
+
+/**
+ * A product the parsers couldn't handle, kept with why so it can be fixed and reprocessed.
+ */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeadLetter {
+    pub product: Product,
+    pub error: String,
+    pub parser_version: String,
+    pub failed_ts: u64,
+}
+
+/**
+ * A directory of dead letters, one JSON file per product ID. A product that fails again
+ * replaces its previous dead letter.
+ */
+#[derive(Clone)]
+pub struct DeadLetters {
+    dir: PathBuf,
+}
+
+impl DeadLetters {
+    pub fn new(dir: &str) -> DeadLetters {
+        DeadLetters {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    pub fn save(&self, product: &Product, error: &ParseError) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.dir)?;
+        let dead_letter = DeadLetter {
+            product: product.clone(),
+            error: format!("{}", error),
+            parser_version: PARSER_VERSION.to_string(),
+            failed_ts: wx::util::get_system_micros(),
+        };
+
+        let path = self.get_path(&product.id);
+        fs::write(&path, serde_json::to_string_pretty(&dead_letter)?)?;
+        Ok(path)
+    }
+
+    /**
+     * Returns every dead letter, oldest product first. Files that can't be read as one, ex.
+     * after a bad hand edit, are logged and skipped so they don't hold up the rest.
+     */
+    pub fn load(&self, logger: &Logger) -> Result<Vec<DeadLetter>, Error> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut dead_letters = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |x| x == "json") {
+                match read_dead_letter(&path) {
+                    Ok(value) => dead_letters.push(value),
+                    Err(error) => {
+                        warn!(logger, "skipping invalid dead letter"; "path" => path.to_string_lossy().to_string(), "error" => format!("{}", error))
+                    }
+                }
+            }
+        }
+
+        dead_letters.sort_by_key(|x| wx::util::ts_to_ticks(&x.product.issuance_time).unwrap_or(0));
+        Ok(dead_letters)
+    }
+
+    pub fn remove(&self, product_id: &str) -> Result<(), Error> {
+        fs::remove_file(self.get_path(product_id))?;
+        Ok(())
+    }
+
+    fn get_path(&self, product_id: &str) -> PathBuf {
+        // IDs are UUIDs, but keep anything else from escaping the directory
+        let name: String = product_id
+            .chars()
+            .map(|x| {
+                if x.is_ascii_alphanumeric() || x == '-' {
+                    x
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+}
+
+/**
+ * Runs every dead letter back through the current parsers, oldest first. Products that parse
+ * now have their events stored and their dead letter removed, and the rest are updated with
+ * the latest error. A dead letter that can't be removed or updated is logged and the rest are
+ * still run. Returns how many were recovered.
+ */
+pub fn reprocess(
+    config: &Config,
+    registry: Arc<Registry>,
+    logger: &Logger,
+) -> Result<usize, Error> {
+    let dead_letters = DeadLetters::new(&config.dead_letter_dir);
+    let sinks = Arc::new(Sinks::from_config(&config.sinks, &config.spool)?);
+    let hazards = Arc::new(Mutex::new(Hazards::new()));
+    // Shared with polling, so reports it already stored aren't stored again
+    let report_log = Arc::new(Mutex::new(ReportLog::load(&config.report_log_path)?));
+    let mut pipeline = Pipeline::new(
+        logger,
+        registry,
+        hazards,
+        report_log,
+        sinks,
+        dead_letters.clone(),
+    );
+    let mut recovered = 0;
+
+    for dead_letter in dead_letters.load(logger)? {
+        let product = &dead_letter.product;
+        match pipeline.try_process(product) {
+            Ok(stored) => {
+                info!(logger, "Recovered product"; "id" => &product.id, "stored" => stored);
+                recovered += 1;
+                if let Err(error) = dead_letters.remove(&product.id) {
+                    error!(logger, "Dead letter error"; "id" => &product.id, "error" => format!("{}", error));
+                }
+            }
+            Err(error) => {
+                warn!(logger, "Still unparseable"; "id" => &product.id, "error" => format!("{}", error));
+                if let Err(error) = dead_letters.save(product, &error) {
+                    error!(logger, "Dead letter error"; "id" => &product.id, "error" => format!("{}", error));
+                }
+            }
+        }
+    }
+
+    Ok(recovered)
+}
+
+fn read_dead_letter(path: &Path) -> Result<DeadLetter, Error> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::{get_logger, get_product_from_file};
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn save_should_be_loaded_oldest_first() {
+        let dir = env::temp_dir().join("nws-api-loader-dead-letters");
+        let _ = fs::remove_dir_all(&dir);
+        let dead_letters = DeadLetters::new(dir.to_str().unwrap());
+        let tor = get_product_from_file("data/products/tor-normal");
+        let svr = get_product_from_file("data/products/svr");
+        let error = ParseError::MissingField {
+            product_id: svr.id.to_string(),
+            product_code: "SVR".to_string(),
+            field: "movement",
+            offset: None,
+        };
+
+        dead_letters.save(&tor, &error).unwrap();
+        dead_letters.save(&svr, &error).unwrap();
+        dead_letters.save(&svr, &error).unwrap();
+
+        let result = dead_letters.load(&get_logger()).unwrap();
+        let ids: Vec<&str> = result.iter().map(|x| x.product.id.as_str()).collect();
+        assert_eq!(vec![svr.id.as_str(), tor.id.as_str()], ids);
+        assert_eq!(PARSER_VERSION, result[0].parser_version);
+        assert!(result[0].error.contains("missing movement"));
+
+        dead_letters.remove(&svr.id).unwrap();
+        assert_eq!(1, dead_letters.load(&get_logger()).unwrap().len());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn load_should_skip_invalid_files() {
+        let dir = env::temp_dir().join("nws-api-loader-dead-letters-invalid");
+        let _ = fs::remove_dir_all(&dir);
+        let dead_letters = DeadLetters::new(dir.to_str().unwrap());
+        let tor = get_product_from_file("data/products/tor-normal");
+        let error = ParseError::MissingField {
+            product_id: tor.id.to_string(),
+            product_code: "TOR".to_string(),
+            field: "movement",
+            offset: None,
+        };
+
+        dead_letters.save(&tor, &error).unwrap();
+        fs::write(dir.join("edited.json"), "{\"product\": ").unwrap();
+
+        let result = dead_letters.load(&get_logger()).unwrap();
+        assert_eq!(1, result.len());
+        assert_eq!(tor.id, result[0].product.id);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,9 +1,22 @@
+use super::lsr_parser::{Correction, LsrEventType};
+use super::pts_parser::PtsHazard;
+use super::tags::WarningTags;
+use super::vtec::Vtec;
+use wx::domain::{Event, OutlookRisk};
+
 #[derive(Debug, Deserialize)]
 pub struct ProductsResult {
     #[serde(rename = "@context")]
     pub _context: Context,
     #[serde(rename = "@graph")]
     pub products: Vec<ListProduct>,
+    // Only present when there are older products than the ones listed
+    pub pagination: Option<Pagination>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Pagination {
+    pub next: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,7 +42,7 @@ pub struct ListProduct {
     product_name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Product {
     #[serde(rename = "@id")]
     pub _id: String,
@@ -47,3 +60,88 @@ pub struct Product {
     #[serde(rename = "productText")]
     pub product_text: String,
 }
+
+/**
+ * An Event along with any NWS-specific details that the shared wx domain doesn't carry yet.
+ * Extensions are flattened on serialization, so JSON consumers expecting a plain Event still
+ * work. The store only takes the Event, see `StoreSink` for how the details reach it.
+ */
+#[derive(Deserialize, Serialize)]
+pub struct NwsEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vtec: Option<Vtec>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_counties: Option<WatchCounties>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lsr: Option<LsrDetails>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<WarningTags>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outlook_area: Option<OutlookArea>,
+}
+
+impl NwsEvent {
+    pub fn has_details(&self) -> bool {
+        self.vtec.is_some()
+            || self.watch_counties.is_some()
+            || self.lsr.is_some()
+            || self.tags.is_some()
+            || self.outlook_area.is_some()
+    }
+}
+
+impl From<Event> for NwsEvent {
+    fn from(event: Event) -> NwsEvent {
+        NwsEvent {
+            event,
+            vtec: None,
+            watch_counties: None,
+            lsr: None,
+            tags: None,
+            outlook_area: None,
+        }
+    }
+}
+
+/**
+ * UGC codes affected by a watch county product, bucketed by VTEC action.
+ */
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct WatchCounties {
+    pub added: Vec<String>,
+    pub continued: Vec<String>,
+    pub extended: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/**
+ * LSR details that don't fit on the shared Report. The ID is built from the office, time,
+ * event type, and location, so it's the same whether the report comes from a preliminary LSR
+ * or a summary that repeats it. A correction can change any of those, so it gets its own ID
+ * and `corrects` is the ID of the report it replaces, once that's been found.
+ */
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct LsrDetails {
+    pub id: String,
+    pub event_type: LsrEventType,
+    pub rating: Option<String>,
+    pub corrects: Option<String>,
+    #[serde(skip)]
+    pub correction: Option<Correction>,
+}
+
+/**
+ * Which area of a PTS outlook an event is for. Probabilistic areas have the chance of the
+ * hazard (ex. 0.05 is 5%), hatched ones are for significant severe weather, and categorical
+ * ones have their risk. Day 4-8 outlooks also say which day they're for.
+ */
+#[derive(Deserialize, Serialize)]
+pub struct OutlookArea {
+    pub hazard: PtsHazard,
+    pub probability: Option<f32>,
+    pub is_significant: bool,
+    pub risk: Option<OutlookRisk>,
+    pub day: Option<u8>,
+}
@@ -0,0 +1,327 @@
+use super::domain::NwsEvent;
+use super::vtec::{Action, Vtec};
+use std::collections::HashMap;
+use std::fs;
+use wx::domain::Coordinates;
+use wx::error::Error;
+
+// Only warnings are tracked, watches are updated county by county and don't map cleanly
+const WARNING_SIGNIFICANCE: &str = "W";
+
+/**
+ * A warning that's currently in effect, as of the latest product issued for it.
+ */
+#[derive(Serialize)]
+pub struct ActiveHazard {
+    pub event_id: String,
+    pub title: String,
+    pub issued_ts: u64,
+    pub updated_ts: u64,
+    pub expires_ts: u64,
+    pub poly: Vec<Coordinates>,
+}
+
+// A warning that was cancelled, expired, or upgraded, kept until it would have expired so
+// products issued before that can't bring it back
+struct EndedHazard {
+    ended_ts: u64,
+    expires_ts: u64,
+}
+
+/**
+ * In-memory registry of active warnings keyed by VTEC event ID (ex. `KMAF.TO.W.0008`). Events
+ * from the warning parsers are applied in the order they're issued, and anything past its
+ * expiration is dropped.
+ */
+#[derive(Default)]
+pub struct Hazards {
+    active: HashMap<String, ActiveHazard>,
+    ended: HashMap<String, EndedHazard>,
+}
+
+impl Hazards {
+    pub fn new() -> Hazards {
+        Hazards::default()
+    }
+
+    /**
+     * Applies the VTEC actions of a product's events. Events without VTEC, or for anything
+     * other than a warning, are ignored, as are events issued before the latest one already
+     * applied to their warning. Follow-ups for a warning that isn't known (ex. it was issued
+     * before the loader started) are treated as new.
+     *
+     * Statements can cancel a warning for some counties and continue it for the rest, so a
+     * warning is only ended if none of the product's segments keep it going.
+     */
+    pub fn apply(&mut self, events: &[NwsEvent]) {
+        let warnings: Vec<(&NwsEvent, &Vtec)> = events
+            .iter()
+            .filter_map(|x| x.vtec.as_ref().map(|vtec| (x, vtec)))
+            .filter(|x| x.1.significance == WARNING_SIGNIFICANCE)
+            .collect();
+
+        for &(event, vtec) in &warnings {
+            let event_id = vtec.event_id();
+            let is_continued = warnings
+                .iter()
+                .any(|x| x.1.event_id() == event_id && !is_ended(x.1.action));
+
+            if is_ended(vtec.action) && !is_continued {
+                self.end(&event_id, event.event.event_ts);
+            } else if !is_ended(vtec.action) {
+                self.update(event_id, event, vtec);
+            }
+        }
+    }
+
+    fn end(&mut self, event_id: &str, ended_ts: u64) {
+        if self.is_stale(event_id, ended_ts) {
+            return;
+        }
+
+        let expires_ts = match self.active.remove(event_id) {
+            Some(value) => value.expires_ts.max(ended_ts),
+            None => ended_ts,
+        };
+
+        self.ended.insert(
+            event_id.to_string(),
+            EndedHazard {
+                ended_ts,
+                expires_ts,
+            },
+        );
+    }
+
+    fn update(&mut self, event_id: String, event: &NwsEvent, vtec: &Vtec) {
+        let updated_ts = event.event.event_ts;
+        if self.is_stale(&event_id, updated_ts) {
+            return;
+        }
+
+        let expires_ts = match vtec.end_ts.or(event.event.expires_ts) {
+            Some(value) => value,
+            None => return,
+        };
+
+        let poly: Vec<Coordinates> = event
+            .event
+            .location
+            .as_ref()
+            .and_then(|x| x.poly.as_ref())
+            .map(|x| {
+                x.iter()
+                    .map(|c| Coordinates {
+                        lat: c.lat,
+                        lon: c.lon,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.ended.remove(&event_id);
+        let hazard = self
+            .active
+            .entry(event_id.to_string())
+            .or_insert_with(|| ActiveHazard {
+                event_id,
+                title: event.event.title.to_string(),
+                issued_ts: vtec.begin_ts.unwrap_or(updated_ts),
+                updated_ts,
+                expires_ts,
+                poly: vec![],
+            });
+
+        hazard.updated_ts = updated_ts;
+        hazard.expires_ts = expires_ts;
+
+        // Statements don't always repeat the polygon, so keep the last known one
+        if !poly.is_empty() {
+            hazard.poly = poly;
+        }
+    }
+
+    // Whether a newer product has already been applied to the warning
+    fn is_stale(&self, event_id: &str, event_ts: u64) -> bool {
+        let updated_ts = self.active.get(event_id).map(|x| x.updated_ts);
+        let ended_ts = self.ended.get(event_id).map(|x| x.ended_ts);
+        updated_ts.or(ended_ts).map_or(false, |x| event_ts < x)
+    }
+
+    /**
+     * Removes and returns every hazard that has expired as of `now` (in micros).
+     */
+    pub fn expire(&mut self, now: u64) -> Vec<ActiveHazard> {
+        self.ended.retain(|_, x| x.expires_ts > now);
+
+        let expired_ids: Vec<String> = self
+            .active
+            .values()
+            .filter(|x| x.expires_ts <= now)
+            .map(|x| x.event_id.to_string())
+            .collect();
+
+        expired_ids
+            .iter()
+            .filter_map(|x| self.active.remove(x))
+            .collect()
+    }
+
+    /**
+     * Returns the hazards in effect as of `now` (in micros), oldest first.
+     */
+    pub fn get_active(&self, now: u64) -> Vec<&ActiveHazard> {
+        let mut active: Vec<&ActiveHazard> = self
+            .active
+            .values()
+            .filter(|x| x.expires_ts > now)
+            .collect();
+        active.sort_by_key(|x| x.issued_ts);
+        active
+    }
+
+    /**
+     * Writes the hazards in effect as of `now` (in micros) to a JSON file, so consumers can
+     * tell which warning polygons are still current. Like checkpoints, it's written to a
+     * temporary file first so readers never see a partial one.
+     */
+    pub fn save(&self, path: &str, now: u64) -> Result<(), Error> {
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, serde_json::to_string(&self.get_active(now))?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn is_ended(action: Action) -> bool {
+    match action {
+        Action::Cancelled | Action::Expired | Action::Upgraded => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::get_product_from_file;
+    use super::super::{parser, svs_parser, tor_parser};
+    use super::*;
+    use parser::Regexes;
+    use std::env;
+
+    fn get_tor_event() -> NwsEvent {
+        let product = get_product_from_file("data/products/tor-normal");
+        let regexes = Regexes::new();
+        tor_parser::parse(&product, regexes).unwrap().unwrap()
+    }
+
+    #[test]
+    fn apply_new_warning_should_be_active() {
+        let events = vec![get_tor_event()];
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+        let active = hazards.get_active(events[0].event.event_ts);
+        assert_eq!(1, active.len());
+        assert_eq!("KMAF.TO.W.0008", active[0].event_id);
+        assert_eq!(Some(active[0].expires_ts), events[0].event.expires_ts);
+    }
+
+    #[test]
+    fn apply_partial_cancel_should_keep_the_remaining_area() {
+        let product = get_product_from_file("data/products/svs-tor");
+        let regexes = Regexes::new();
+        let mut events = svs_parser::parse(&product, regexes).unwrap();
+        assert_eq!(
+            Some(Action::Cancelled),
+            events[0].vtec.as_ref().map(|x| x.action)
+        );
+
+        // The order of the segments shouldn't matter
+        for _ in 0..2 {
+            let mut hazards = Hazards::new();
+            hazards.apply(&events);
+            let active = hazards.get_active(events[1].event.event_ts);
+            assert_eq!(1, active.len());
+            assert_eq!("KAKQ.TO.W.0036", active[0].event_id);
+            assert_eq!(8, active[0].poly.len());
+            events.reverse();
+        }
+    }
+
+    #[test]
+    fn apply_cancel_should_end_the_warning() {
+        let mut events = vec![get_tor_event()];
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+
+        let event_ts = events[0].event.event_ts;
+        events[0].event.event_ts += 60_000_000;
+        events[0].vtec.as_mut().unwrap().action = Action::Cancelled;
+        hazards.apply(&events);
+        assert!(hazards.get_active(event_ts).is_empty());
+
+        // The original warning showing up late shouldn't bring it back
+        events[0].event.event_ts = event_ts;
+        events[0].vtec.as_mut().unwrap().action = Action::New;
+        hazards.apply(&events);
+        assert!(hazards.get_active(event_ts).is_empty());
+    }
+
+    #[test]
+    fn apply_older_event_should_be_ignored() {
+        let mut events = vec![get_tor_event()];
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+
+        let event_ts = events[0].event.event_ts;
+        events[0].event.event_ts -= 60_000_000;
+        events[0].vtec.as_mut().unwrap().action = Action::Cancelled;
+        hazards.apply(&events);
+        let active = hazards.get_active(event_ts);
+        assert_eq!(1, active.len());
+        assert_eq!(event_ts, active[0].updated_ts);
+    }
+
+    #[test]
+    fn expire_should_remove_past_warnings() {
+        let events = vec![get_tor_event()];
+        let expires_ts = events[0].event.expires_ts.unwrap();
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+        assert!(hazards.expire(expires_ts - 1).is_empty());
+        let expired = hazards.expire(expires_ts);
+        assert_eq!(1, expired.len());
+        assert!(hazards.get_active(expires_ts).is_empty());
+    }
+
+    #[test]
+    fn apply_watch_should_be_ignored() {
+        let product = get_product_from_file("data/products/wou-tor-watch");
+        let regexes = Regexes::new();
+        let events: Vec<NwsEvent> = super::super::wou_parser::parse(&product, regexes)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+        assert!(hazards.get_active(events[0].event.event_ts).is_empty());
+    }
+
+    #[test]
+    fn save_should_write_the_active_hazards() {
+        let path = env::temp_dir().join("nws-api-loader-hazards.json");
+        let path = path.to_str().unwrap();
+        let events = vec![get_tor_event()];
+        let mut hazards = Hazards::new();
+        hazards.apply(&events);
+        hazards.save(path, events[0].event.event_ts).unwrap();
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!("KMAF.TO.W.0008", saved[0]["event_id"]);
+        hazards
+            .save(path, events[0].event.expires_ts.unwrap())
+            .unwrap();
+        assert_eq!("[]", fs::read_to_string(path).unwrap());
+        let _ = fs::remove_file(path);
+    }
+}
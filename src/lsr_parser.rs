@@ -1,76 +1,300 @@
-use super::domain::Product;
+use super::domain::{LsrDetails, NwsEvent, Product};
+use super::parser::{get_issuance_ticks, get_offset, FieldError};
 use chrono::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::slice::SliceIndex;
 use wx::domain::{Coordinates, Event, EventType, HazardType, Location, Report, Units};
-use wx::error::{Error, WxError};
+use wx::error::Error;
 use wx::util;
 
 const AGE_THRESHOLD_MICROS: u64 = 60 * 60 * 1000 * 1000;
+// How far a correction can move a report's time and still be matched to it
+const CORRECTION_WINDOW_MICROS: u64 = 6 * 60 * 60 * 1000 * 1000;
+const REPORT_LOG_SIZE: usize = 10_000;
+const MPH_PER_KT: f32 = 1.150_78;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LsrKind {
+    Preliminary,
+    Summary,
+    Correction,
+}
 
 // Intermediary structure for an LSR to make parsing easier
 #[derive(Debug)]
 struct Skeleton<'a> {
-    top_line: &'a str,
-    bottom_line: &'a str,
+    kind: LsrKind,
     lines: Vec<&'a str>,
+    blocks: Vec<Block<'a>>,
     remarks_index: usize,
     end_index: usize,
 }
 
-pub fn parse(product: &Product) -> Result<Option<Event>, Error> {
+// Decoded magnitude of a single report
+#[derive(Default)]
+struct Magnitude {
+    value: Option<f32>,
+    units: Option<Units>,
+    was_measured: Option<bool>,
+    rating: Option<String>,
+}
+
+// A single report within an LSR, made up of two fixed-width lines of details and any remarks
+#[derive(Debug)]
+struct Block<'a> {
+    top_line: &'a str,
+    bottom_line: &'a str,
+    remarks: Option<String>,
+}
+
+/**
+ * The report a correction replaces, as far as the correction can tell. The remarks usually
+ * say, ex. `CORRECTS PREVIOUS TSTM WND DMG REPORT FROM 3 SSW VICKSBURG`, otherwise the
+ * correction's own event and city are the best guess.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Correction {
+    pub event: String,
+    pub city: String,
+}
+
+/**
+ * Remembers the IDs of reports that have already been emitted, so summaries (which repeat
+ * earlier reports) only pass along the ones that are new, and corrections can find the report
+ * they replace. Only the most recent IDs are kept. When loaded from a file, it's saved back
+ * there so a restart doesn't re-emit every report a summary repeats.
+ */
+#[derive(Default)]
+pub struct ReportLog {
+    path: Option<String>,
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl ReportLog {
+    pub fn new() -> ReportLog {
+        ReportLog::default()
+    }
+
+    /**
+     * Reads the IDs saved by a previous run, starting empty if the file doesn't exist yet.
+     */
+    pub fn load(path: &str) -> Result<ReportLog, Error> {
+        let ids: Vec<String> = if Path::new(path).exists() {
+            serde_json::from_str(&fs::read_to_string(path)?)?
+        } else {
+            vec![]
+        };
+
+        let mut report_log = ReportLog {
+            path: Some(path.to_string()),
+            ..ReportLog::default()
+        };
+        for id in ids {
+            report_log.log(&id);
+        }
+
+        Ok(report_log)
+    }
+
+    /**
+     * Rewrites the file the IDs were loaded from, oldest first, if there was one. Like
+     * checkpoints, it's written to a temporary file first.
+     */
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, serde_json::to_string(&self.order)?)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /**
+     * Returns false if the event is a report that's already been logged, otherwise logs it and
+     * returns true. Corrections always pass, and are pointed at the report they replace if
+     * it's been logged. Non-LSR events always pass.
+     */
+    pub fn insert(&mut self, event: &mut NwsEvent) -> bool {
+        let lsr = match &mut event.lsr {
+            Some(value) => value,
+            None => return true,
+        };
+
+        if let Some(correction) = &lsr.correction {
+            lsr.corrects = self.find_original(&lsr.id, correction);
+            self.log(&lsr.id);
+            return true;
+        }
+
+        self.log(&lsr.id)
+    }
+
+    fn log(&mut self, id: &str) -> bool {
+        if !self.ids.insert(id.to_string()) {
+            return false;
+        }
+
+        self.order.push_back(id.to_string());
+        if self.order.len() > REPORT_LOG_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    /**
+     * A correction usually fixes one thing, so the original is the logged report from the same
+     * office, within the window, that agrees with it on at least two of the time, event, and
+     * city. The closest match wins.
+     */
+    fn find_original(&self, id: &str, correction: &Correction) -> Option<String> {
+        let (wfo, report_ts, _, _) = split_id(id)?;
+
+        self.order
+            .iter()
+            .filter_map(|x| {
+                let (original_wfo, original_ts, event, city) = split_id(x)?;
+                let distance = if report_ts > original_ts {
+                    report_ts - original_ts
+                } else {
+                    original_ts - report_ts
+                };
+
+                if original_wfo != wfo || distance > CORRECTION_WINDOW_MICROS {
+                    return None;
+                }
+
+                let matches = [
+                    distance == 0,
+                    event == correction.event,
+                    city == correction.city,
+                ]
+                .iter()
+                .filter(|x| **x)
+                .count();
+                if matches < 2 {
+                    return None;
+                }
+
+                Some(((3 - matches, distance), x))
+            })
+            .min_by_key(|x| x.0)
+            .map(|x| x.1.to_string())
+    }
+}
+
+// Reads the office, time, event, and city back out of a report ID
+fn split_id(id: &str) -> Option<(&str, u64, &str, &str)> {
+    let mut parts = id.splitn(4, '.');
+    let wfo = parts.next()?;
+    let report_ts = parts.next()?.parse().ok()?;
+    Some((wfo, report_ts, parts.next()?, parts.next()?))
+}
+
+/**
+ * Parses every report in an LSR. Most products have a single report, but offices will batch
+ * them up during busy events. Summaries repeat reports that were already sent and corrections
+ * reference the report they replace, so both are exempt from the age threshold.
+ */
+pub fn parse(product: &Product) -> Result<Vec<NwsEvent>, FieldError> {
     let text = &product.product_text;
-    let skeleton = get_skeleton(&text)?;
+    let lsr = get_skeleton(&text)?;
+    let event_ts = get_issuance_ticks(product)?;
+    // ex. `701 PM CDT TUE MAY 1 2018`
+    let issued_line = lsr.lines.get(7).copied().unwrap_or("");
+    let tz = issued_line
+        .split(' ')
+        .nth(2)
+        .ok_or_else(|| FieldError::missing("time zone").at(get_offset(&text, issued_line)))?;
+    let offset = util::tz_to_offset(tz)
+        .map_err(|_| FieldError::invalid("time zone", tz).at(get_offset(&text, tz)))?;
+    let mut events = vec![];
 
-    if skeleton.is_none() {
-        return Ok(None);
+    for block in &lsr.blocks {
+        if let Some(event) = get_event(product, lsr.kind, block, event_ts, offset)? {
+            events.push(event);
+        }
     }
 
-    let lsr = skeleton.unwrap();
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
-    let raw_ts = lsr.bottom_line.get(0..10).unwrap().to_string() + lsr.top_line.get(0..7).unwrap();
-    let offset: Vec<&str> = lsr.lines[7].split(' ').collect();
-    let offset = util::tz_to_offset(offset[2])?;
-    let raw_ts = raw_ts + offset;
-    let report_ts = get_report_ticks(&raw_ts)?;
+    Ok(events)
+}
+
+fn get_event(
+    product: &Product,
+    kind: LsrKind,
+    block: &Block,
+    event_ts: u64,
+    offset: &str,
+) -> Result<Option<NwsEvent>, FieldError> {
+    let text = &product.product_text;
+    let date = get_column(text, block.bottom_line, 0..10, "date")?;
+    let time = get_column(text, block.top_line, 0..7, "time")?;
+    let raw_ts = date.to_string() + time + offset;
+    let report_ts =
+        get_report_ticks(&raw_ts).map_err(|x| x.at(get_offset(&text, block.top_line)))?;
 
     // Skip reports too far in the past, especially since these can come hours, days, or even months later
-    if event_ts - report_ts > AGE_THRESHOLD_MICROS {
+    if kind == LsrKind::Preliminary && event_ts.saturating_sub(report_ts) > AGE_THRESHOLD_MICROS {
         return Ok(None);
     }
 
-    let raw_point = lsr.top_line.get(53..).unwrap().replace("W", "");
-    let raw_point = raw_point.trim();
-    let lon: f32 = raw_point.get(7..).unwrap().trim().parse().unwrap();
-    let lon = lon * -1.0;
+    let raw_point = get_column(text, block.top_line, 53.., "lat.lon")?;
+    let invalid_point =
+        || FieldError::invalid("lat.lon", raw_point).at(get_offset(&text, raw_point));
+    let point = raw_point.replace("W", "");
+    let point = point.trim();
+    let lat: f32 = point
+        .get(0..5)
+        .and_then(|x| x.parse().ok())
+        .ok_or_else(invalid_point)?;
+    let lon: f32 = point
+        .get(7..)
+        .and_then(|x| x.trim().parse().ok())
+        .ok_or_else(invalid_point)?;
     let point = Some(Coordinates {
-        lat: raw_point.get(0..5).unwrap().parse()?,
-        lon,
+        lat,
+        lon: lon * -1.0,
     });
 
     let wfo = &product.issuing_office;
-    let raw_hazard = lsr.top_line.get(12..29).unwrap().trim();
-    let hazard = get_lsr_hazard_type(raw_hazard);
-    let mut was_measured = None;
-    let mut units = None;
-    let mut magnitude = None;
-    let raw_mag = lsr.bottom_line.get(12..29).unwrap().trim();
-    let county = lsr.bottom_line.get(29..48).unwrap().trim().to_string();
-    let mut title = "Report: ".to_string();
-
-    if !raw_mag.is_empty() {
-        was_measured = Some(raw_mag.get(0..1).unwrap() == "M");
-        let space_index = raw_mag.find(' ').unwrap();
-        if raw_mag.contains("MPH") {
-            units = Some(Units::Mph);
-            magnitude = Some(raw_mag.get(1..space_index).unwrap().parse().unwrap());
-            title = format!("{} {}mph", title, magnitude.unwrap());
-        } else if raw_mag.contains("INCH") {
-            units = Some(Units::Inches);
-            magnitude = Some(raw_mag.get(1..space_index).unwrap().parse().unwrap());
-            title = format!("{} {}\"", title, magnitude.unwrap());
+    let raw_hazard = get_column(text, block.top_line, 12..29, "event")?.trim();
+    let event_type = get_lsr_event_type(raw_hazard);
+    let hazard = event_type.get_hazard_type();
+    let raw_mag = get_column(text, block.bottom_line, 12..29, "mag")?.trim();
+    let magnitude = get_magnitude(raw_mag);
+    let city = get_column(text, block.top_line, 29..53, "city location")?.trim();
+    let county = get_column(text, block.bottom_line, 29..48, "county location")?
+        .trim()
+        .to_string();
+    let mut title = match kind {
+        LsrKind::Correction => "Corrected Report: ".to_string(),
+        _ => "Report: ".to_string(),
+    };
+
+    if let Some(rating) = &magnitude.rating {
+        title = format!("{} {}", title, rating);
+    } else if let Some(value) = magnitude.value {
+        match magnitude.units {
+            Some(Units::Mph) => title = format!("{} {}mph", title, value),
+            Some(Units::Inches) => title = format!("{} {}\"", title, value),
+            _ => (),
         }
     }
 
-    let title = format!("{} {:?} ({})", title, hazard, wfo);
+    // The shared HazardType is coarse, so fall back to the LSR's own name when it has no match
+    let hazard_name = match hazard {
+        HazardType::Other => to_title_case(raw_hazard),
+        _ => format!("{:?}", hazard),
+    };
+    let title = format!("{} {} ({})", title, hazard_name, wfo);
 
     let location = Location {
         point,
@@ -81,39 +305,80 @@ pub fn parse(product: &Product) -> Result<Option<Event>, Error> {
 
     // CO-OP OBSERVER, TRAINED SPOTTER, STORM CHASER, PUBLIC, EMERGENCY MNGR, ASOS, AWOS,
     // NWS EMPLOYEE, OFFICIAL NWS OBS, NWS STORM SURVEY, AMATEUR RADIO, BROADCAST MEDIA, etc.
-    let reporter = lsr.bottom_line.get(53..).unwrap().trim().to_string();
+    let reporter = get_column(text, block.bottom_line, 53.., "source")?
+        .trim()
+        .to_string();
 
     let report = Report {
         hazard,
-        magnitude,
+        magnitude: magnitude.value,
         report_ts: Some(report_ts),
         reporter,
-        units,
-        was_measured,
+        units: magnitude.units,
+        was_measured: magnitude.was_measured,
     };
 
     let mut event = Event::new(event_ts, EventType::NwsLsr, title);
     event.location = Some(location);
     event.report = Some(report);
-    event.text = Some(text.to_string());
+    // Only this report's remarks, since a summary's full text covers every report in it
+    event.text = block.remarks.clone();
+
+    let id = format!("{}.{}.{}.{}", wfo, report_ts, raw_hazard, city);
+    let correction = match kind {
+        LsrKind::Correction => Some(
+            block
+                .remarks
+                .as_ref()
+                .and_then(|x| get_correction(x))
+                .unwrap_or_else(|| Correction {
+                    event: raw_hazard.to_string(),
+                    city: city.to_string(),
+                }),
+        ),
+        _ => None,
+    };
+
+    Ok(Some(NwsEvent {
+        lsr: Some(LsrDetails {
+            id,
+            event_type,
+            rating: magnitude.rating,
+            corrects: None,
+            correction,
+        }),
+        ..NwsEvent::from(event)
+    }))
+}
 
-    Ok(Some(event))
+// Reads a fixed-width column from one of a report's lines, ex. the EVENT column from `12..29`
+fn get_column<'a, I>(
+    text: &str,
+    line: &'a str,
+    range: I,
+    field: &'static str,
+) -> Result<&'a str, FieldError>
+where
+    I: SliceIndex<str, Output = str>,
+{
+    line.get(range)
+        .ok_or_else(|| FieldError::missing(field).at(get_offset(text, line)))
 }
 
-fn get_skeleton(text: &str) -> Result<Option<Skeleton>, Error> {
+fn get_skeleton(text: &str) -> Result<Skeleton, FieldError> {
     let lines: Vec<&str> = text.lines().collect();
 
     if lines.len() < 16 {
-        return Err(Error::Wx(<WxError>::new("invalid LSR body: too few lines")));
-    }
-
-    if lines[5].contains("SUMMARY") {
-        return Ok(None);
+        return Err(FieldError::missing("details"));
     }
 
-    if lines[5].contains("CORRECTED") {
-        return Ok(None);
-    }
+    let kind = if lines[5].contains("SUMMARY") {
+        LsrKind::Summary
+    } else if lines[5].contains("CORRECTED") {
+        LsrKind::Correction
+    } else {
+        LsrKind::Preliminary
+    };
 
     let mut remarks_index = None;
     let mut end_index = None;
@@ -134,58 +399,328 @@ fn get_skeleton(text: &str) -> Result<Option<Skeleton>, Error> {
     }
 
     // ...and if it doesn't we really don't want things to blow up.
-    if end_index.is_none() {
-        end_index = Some(lines.len() - 1);
-    }
+    let end_index = end_index.unwrap_or(lines.len() - 1);
+    let remarks_index = remarks_index.ok_or_else(|| FieldError::missing("remarks"))?;
+    let mut blocks = vec![];
+
+    // Each report starts with a time line immediately followed by a date line
+    for i in remarks_index + 1..end_index {
+        let (top_line, bottom_line) = match (lines.get(i), lines.get(i + 1)) {
+            (Some(top_line), Some(bottom_line)) => (*top_line, *bottom_line),
+            _ => break,
+        };
 
-    if remarks_index.is_none() {
-        return Err(Error::Wx(<WxError>::new(
-            "invalid LSR body: missing REMARKS",
-        )));
+        if !is_time(top_line) || !is_date(bottom_line) {
+            continue;
+        }
+
+        if top_line.len() < 53 || bottom_line.len() < 53 {
+            return Err(FieldError::missing("details").at(get_offset(text, top_line)));
+        }
+
+        blocks.push(Block {
+            top_line,
+            bottom_line,
+            remarks: get_remarks(lines.get(i + 2..end_index).unwrap_or(&[])),
+        });
     }
 
-    let remarks_index = remarks_index.unwrap();
-    let end_index = end_index.unwrap();
-    let top_line = lines[remarks_index + 2];
-    let bottom_line = lines[remarks_index + 3];
-    if top_line.len() < 53 || bottom_line.len() < 53 {
-        return Err(Error::Wx(<WxError>::new(
-            "invalid LSR body: missing details",
-        )));
+    if blocks.is_empty() {
+        return Err(FieldError::missing("details"));
     }
 
-    Ok(Some(Skeleton {
-        bottom_line,
-        top_line,
+    Ok(Skeleton {
+        kind,
+        lines,
+        blocks,
         remarks_index,
         end_index,
-        lines,
-    }))
+    })
+}
+
+/**
+ * Remarks are an indented paragraph after the details, wrapped to a fixed width. Reports
+ * without any go straight to a blank line or the next report.
+ */
+fn get_remarks(lines: &[&str]) -> Option<String> {
+    let remarks: Vec<&str> = lines
+        .iter()
+        .take_while(|x| !is_time(x))
+        .map(|x| x.trim())
+        .skip_while(|x| x.is_empty())
+        .take_while(|x| !x.is_empty())
+        .collect();
+
+    if remarks.is_empty() {
+        return None;
+    }
+
+    // Some offices' software escapes quotes, ex. `1&QUOT;/H`
+    Some(remarks.join(" ").replace("&QUOT;", "\""))
+}
+
+/**
+ * Reads the report a correction replaces from its remarks, ex. `CORRECTS PREVIOUS TSTM WND DMG
+ * REPORT FROM 3 SSW VICKSBURG.`
+ */
+fn get_correction(remarks: &str) -> Option<Correction> {
+    let start = remarks.find("CORRECTS PREVIOUS ")? + "CORRECTS PREVIOUS ".len();
+    let rest = &remarks[start..];
+    let split = rest.find(" REPORT FROM ")?;
+    let city = &rest[split + " REPORT FROM ".len()..];
+    let city = city.split('.').next().unwrap_or(city);
+
+    Some(Correction {
+        event: rest[..split].trim().to_string(),
+        city: city.trim().to_string(),
+    })
+}
+
+// ex. `0700 PM`
+fn is_time(line: &str) -> bool {
+    match line.get(0..7) {
+        Some(value) => {
+            value.chars().take(4).all(|x| x.is_ascii_digit())
+                && (value.ends_with(" AM") || value.ends_with(" PM"))
+        }
+        None => false,
+    }
+}
+
+// ex. `05/01/2018`
+fn is_date(line: &str) -> bool {
+    match line.get(0..10) {
+        Some(value) => value.chars().enumerate().all(|(i, x)| match i {
+            2 | 5 => x == '/',
+            _ => x.is_ascii_digit(),
+        }),
+        None => false,
+    }
+}
+
+/**
+ * Event types from NWS Directive 10-517. Offices occasionally use their own, which are kept
+ * as-is.
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum LsrEventType {
+    Avalanche,
+    Blizzard,
+    CoastalFlood,
+    DebrisFlow,
+    DenseFog,
+    Downburst,
+    Drought,
+    DustStorm,
+    ExcessiveHeat,
+    ExtremeCold,
+    ExtremeWindChill,
+    FlashFlood,
+    Flood,
+    Freeze,
+    FreezingDrizzle,
+    FreezingRain,
+    FunnelCloud,
+    Hail,
+    HeavyRain,
+    HeavySleet,
+    HeavySnow,
+    HighAstronomicalTides,
+    HighSurf,
+    HighSustainedWinds,
+    Hurricane,
+    IceStorm,
+    LakeshoreFlood,
+    Lightning,
+    LowAstronomicalTides,
+    MarineHail,
+    MarineThunderstormWind,
+    NonThunderstormWindDamage,
+    NonThunderstormWindGust,
+    RipCurrents,
+    Seiche,
+    Sleet,
+    Snow,
+    SnowSquall,
+    StormSurge,
+    ThunderstormWindDamage,
+    ThunderstormWindGust,
+    Tornado,
+    TropicalStorm,
+    Tsunami,
+    VolcanicAshfall,
+    Waterspout,
+    Wildfire,
+    Other(String),
 }
 
-fn get_lsr_hazard_type(input: &str) -> HazardType {
+impl LsrEventType {
+    fn get_hazard_type(&self) -> HazardType {
+        match self {
+            LsrEventType::Tornado => HazardType::Tornado,
+            LsrEventType::Hail | LsrEventType::MarineHail => HazardType::Hail,
+            LsrEventType::Flood
+            | LsrEventType::FlashFlood
+            | LsrEventType::CoastalFlood
+            | LsrEventType::LakeshoreFlood => HazardType::Flood,
+            LsrEventType::FreezingRain | LsrEventType::FreezingDrizzle | LsrEventType::IceStorm => {
+                HazardType::FreezingRain
+            }
+            LsrEventType::ThunderstormWindGust
+            | LsrEventType::ThunderstormWindDamage
+            | LsrEventType::NonThunderstormWindGust
+            | LsrEventType::NonThunderstormWindDamage
+            | LsrEventType::MarineThunderstormWind
+            | LsrEventType::HighSustainedWinds
+            | LsrEventType::Downburst => HazardType::Wind,
+            _ => HazardType::Other,
+        }
+    }
+}
+
+fn get_lsr_event_type(input: &str) -> LsrEventType {
     match input {
-        "TORNADO" => HazardType::Tornado,
-        "HAIL" => HazardType::Hail,
-        "FLOOD" => HazardType::Flood,
-        "FREEZING RAIN" => HazardType::FreezingRain,
-        "TSTM WND GST" => HazardType::Wind,
-        "TSTM WND DMG" => HazardType::Wind,
-        "NON-TSTM WND GST" => HazardType::Wind,
-        "NON-TSTM WND DMG" => HazardType::Wind,
-        "MARINE TSTM WIND" => HazardType::Wind,
-        _ => HazardType::Other,
+        "AVALANCHE" => LsrEventType::Avalanche,
+        "BLIZZARD" => LsrEventType::Blizzard,
+        "COASTAL FLOOD" => LsrEventType::CoastalFlood,
+        "DEBRIS FLOW" => LsrEventType::DebrisFlow,
+        "DENSE FOG" => LsrEventType::DenseFog,
+        "DOWNBURST" => LsrEventType::Downburst,
+        "DROUGHT" => LsrEventType::Drought,
+        "DUST STORM" => LsrEventType::DustStorm,
+        "EXCESSIVE HEAT" => LsrEventType::ExcessiveHeat,
+        "EXTREME COLD" => LsrEventType::ExtremeCold,
+        "EXTR WIND CHILL" => LsrEventType::ExtremeWindChill,
+        "FLASH FLOOD" => LsrEventType::FlashFlood,
+        "FLOOD" => LsrEventType::Flood,
+        "FREEZE" => LsrEventType::Freeze,
+        "FREEZING DRIZZLE" => LsrEventType::FreezingDrizzle,
+        "FREEZING RAIN" => LsrEventType::FreezingRain,
+        "FUNNEL CLOUD" => LsrEventType::FunnelCloud,
+        "HAIL" => LsrEventType::Hail,
+        "HEAVY RAIN" => LsrEventType::HeavyRain,
+        "HEAVY SLEET" => LsrEventType::HeavySleet,
+        "HEAVY SNOW" => LsrEventType::HeavySnow,
+        "HIGH ASTR TIDES" => LsrEventType::HighAstronomicalTides,
+        "HIGH SURF" => LsrEventType::HighSurf,
+        "HIGH SUST WINDS" => LsrEventType::HighSustainedWinds,
+        "HURRICANE" => LsrEventType::Hurricane,
+        "ICE STORM" => LsrEventType::IceStorm,
+        "LAKESHORE FLOOD" => LsrEventType::LakeshoreFlood,
+        "LIGHTNING" => LsrEventType::Lightning,
+        "LOW ASTR TIDES" => LsrEventType::LowAstronomicalTides,
+        "MARINE HAIL" => LsrEventType::MarineHail,
+        "MARINE TSTM WIND" => LsrEventType::MarineThunderstormWind,
+        "NON-TSTM WND DMG" => LsrEventType::NonThunderstormWindDamage,
+        "NON-TSTM WND GST" => LsrEventType::NonThunderstormWindGust,
+        "RIP CURRENTS" => LsrEventType::RipCurrents,
+        "SEICHE" => LsrEventType::Seiche,
+        "SLEET" => LsrEventType::Sleet,
+        "SNOW" => LsrEventType::Snow,
+        "SNOW SQUALL" => LsrEventType::SnowSquall,
+        "STORM SURGE" => LsrEventType::StormSurge,
+        "TSTM WND DMG" => LsrEventType::ThunderstormWindDamage,
+        "TSTM WND GST" => LsrEventType::ThunderstormWindGust,
+        "TORNADO" => LsrEventType::Tornado,
+        "TROPICAL STORM" => LsrEventType::TropicalStorm,
+        "TSUNAMI" => LsrEventType::Tsunami,
+        "VOLCANIC ASHFALL" => LsrEventType::VolcanicAshfall,
+        "WATER SPOUT" => LsrEventType::Waterspout,
+        "WILDFIRE" => LsrEventType::Wildfire,
+        _ => LsrEventType::Other(input.to_string()),
     }
 }
 
-fn get_report_ticks(input: &str) -> Result<u64, Error> {
-    Ok(DateTime::parse_from_str(input, "%m/%d/%Y%I%M %p%z")?.timestamp_millis() as u64 * 1000)
+/**
+ * Decodes the MAG column. Measurements are prefixed with `M` (measured), `E` (estimated), or
+ * `U` (unknown), ex. `E1.25 INCH` or `M52 KTS`. Tornadoes are rated instead, ex. `EF2`.
+ * Knots are converted to mph since the shared Units don't include them, and magnitudes in
+ * any other units (feet of surf, acres burned, etc.) are kept without units.
+ */
+fn get_magnitude(input: &str) -> Magnitude {
+    let mut magnitude = Magnitude::default();
+
+    if is_rating(input) {
+        magnitude.rating = Some(input.to_string());
+        magnitude.value = input
+            .trim_start_matches('E')
+            .get(1..)
+            .and_then(|x| x.parse().ok());
+        return magnitude;
+    }
+
+    let raw_value = match input.get(0..1) {
+        Some("M") => {
+            magnitude.was_measured = Some(true);
+            &input[1..]
+        }
+        Some("E") => {
+            magnitude.was_measured = Some(false);
+            &input[1..]
+        }
+        Some("U") => &input[1..],
+        _ => input,
+    };
+
+    let mut splits = raw_value.split_whitespace();
+    let value: Option<f32> = splits.next().and_then(|x| x.parse().ok());
+
+    match splits.next() {
+        Some("MPH") => {
+            magnitude.units = Some(Units::Mph);
+            magnitude.value = value;
+        }
+        Some("KT") | Some("KTS") => {
+            magnitude.units = Some(Units::Mph);
+            magnitude.value = value.map(|x| (x * MPH_PER_KT).round());
+        }
+        Some("IN") | Some("INCH") | Some("INCHES") => {
+            magnitude.units = Some(Units::Inches);
+            magnitude.value = value;
+        }
+        _ => magnitude.value = value,
+    }
+
+    magnitude
+}
+
+// ex. `F3`, `EF0`, or `EFU` for tornadoes that couldn't be rated
+fn is_rating(input: &str) -> bool {
+    let rating = input.trim_start_matches('E');
+    input.len() <= 3
+        && rating.len() == 2
+        && rating.starts_with('F')
+        && rating.ends_with(|x: char| x.is_ascii_digit() || x == 'U')
+}
+
+// ex. `FUNNEL CLOUD` to `Funnel Cloud`
+fn to_title_case(input: &str) -> String {
+    input
+        .split(' ')
+        .map(|x| {
+            let lower = x.to_lowercase();
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn get_report_ticks(input: &str) -> Result<u64, FieldError> {
+    match DateTime::parse_from_str(input, "%m/%d/%Y%I%M %p%z") {
+        Ok(value) => Ok(value.timestamp_millis() as u64 * 1000),
+        Err(_) => Err(FieldError::invalid("report time", input)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::test_util::get_product_from_file;
     use super::*;
+    use std::env;
 
     #[test]
     fn get_report_ticks_should_return_correct_ticks() {
@@ -202,17 +737,19 @@ mod tests {
     }
 
     #[test]
-    fn get_skeleton_summary_should_be_an_ok_none() {
+    fn get_skeleton_summary() {
         let product = get_product_from_file("data/products/lsr-summary");
         let result = get_skeleton(&product.product_text).unwrap();
-        assert!(result.is_none());
+        assert_eq!(LsrKind::Summary, result.kind);
+        assert_eq!(36, result.blocks.len());
     }
 
     #[test]
-    fn get_skeleton_corrected_should_be_an_error() {
+    fn get_skeleton_corrected() {
         let product = get_product_from_file("data/products/lsr-corrected-tstm-wind-dmg");
         let result = get_skeleton(&product.product_text).unwrap();
-        assert!(result.is_none());
+        assert_eq!(LsrKind::Correction, result.kind);
+        assert_eq!(1, result.blocks.len());
     }
 
     #[test]
@@ -254,24 +791,24 @@ mod tests {
     fn parse_tornado_report() {
         let product = get_product_from_file("data/products/lsr-tornado");
         let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1522524900000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KMFL","point":{"lat":26.8,"lon":-80.64},"poly":null,"county":"PALM BEACH"},"md":null,"outlook":null,"report":{"reporter":"TRAINED SPOTTER","hazard":"Tornado","magnitude":null,"units":null,"was_measured":null,"report_ts":1522522800000000},"text":"\n158 \nNWUS52 KMFL 311935\nLSRMFL\n\nPRELIMINARY LOCAL STORM REPORT\nNATIONAL WEATHER SERVICE MIAMI FL\n335 PM EDT SAT MAR 31 2018\n\n..TIME...   ...EVENT...      ...CITY LOCATION...     ...LAT.LON...\n..DATE...   ....MAG....      ..COUNTY LOCATION..ST.. ...SOURCE....\n            ..REMARKS..\n\n0300 PM     TORNADO          2 SE PAHOKEE            26.80N  80.64W\n03/31/2018                   PALM BEACH         FL   TRAINED SPOTTER \n\n            TRAINED SKYWARN SPOTTER OBSERVED FROM PAHOKEE A FUNNEL \n            CLOUD APPROXIMATELY 3 MILES SOUTHEAST OF PAHOKEE, \n            PARTIALLY RAIN-WRAPPED AND NEARLY STATIONARY. THE FUNNEL \n            EXTENDED TO NEARLY HALFWAY TO THE GROUND BEFORE LIFTING. \n            LOCATION RADAR-ESTIMATED/ADJUSTED. VIDEO RECEIVED OF \n            FUNNEL REACHING THE GROUND WITH DUST BEING KICKED UP. \n            RECLASSIFIED AS A TORNADO. \n\n\n&&\nEVENT...FATALITIES...INJURIES...REMARKS\n\nEVENT NUMBER MFL1800020\n\n$$\n\nSI\n\n\n\n","title":"Report:  Tornado (KMFL)","valid_ts":null,"warning":null,"watch":null}"#;
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1522524900000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KMFL","point":{"lat":26.8,"lon":-80.64},"poly":null,"county":"PALM BEACH"},"md":null,"outlook":null,"report":{"reporter":"TRAINED SPOTTER","hazard":"Tornado","magnitude":null,"units":null,"was_measured":null,"report_ts":1522522800000000},"text":"TRAINED SKYWARN SPOTTER OBSERVED FROM PAHOKEE A FUNNEL CLOUD APPROXIMATELY 3 MILES SOUTHEAST OF PAHOKEE, PARTIALLY RAIN-WRAPPED AND NEARLY STATIONARY. THE FUNNEL EXTENDED TO NEARLY HALFWAY TO THE GROUND BEFORE LIFTING. LOCATION RADAR-ESTIMATED/ADJUSTED. VIDEO RECEIVED OF FUNNEL REACHING THE GROUND WITH DUST BEING KICKED UP. RECLASSIFIED AS A TORNADO.","title":"Report:  Tornado (KMFL)","valid_ts":null,"warning":null,"watch":null,"lsr":{"id":"KMFL.1522522800000000.TORNADO.2 SE PAHOKEE","event_type":"Tornado","rating":null,"corrects":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
     #[test]
-    fn parse_old_report_should_be_ok_none() {
+    fn parse_old_report_should_be_empty() {
         let product = get_product_from_file("data/products/lsr-tornado-old");
         let result = parse(&product).unwrap();
-        assert!(result.is_none());
+        assert!(result.is_empty());
     }
 
     #[test]
     fn parse_wind_speed_report() {
         let product = get_product_from_file("data/products/lsr-tstm-wind");
         let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1555316100000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KMHX","point":{"lat":35.07,"lon":-77.04},"poly":null,"county":"CRAVEN"},"md":null,"outlook":null,"report":{"reporter":"ASOS","hazard":"Wind","magnitude":61.0,"units":"Mph","was_measured":true,"report_ts":1555315080000000},"text":"\n000\nNWUS52 KMHX 150815\nLSRMHX\n\nPRELIMINARY LOCAL STORM REPORT\nNATIONAL WEATHER SERVICE NEWPORT/MOREHEAD CITY NC\n415 AM EDT MON APR 15 2019\n\n..TIME...   ...EVENT...      ...CITY LOCATION...     ...LAT.LON...\n..DATE...   ....MAG....      ..COUNTY LOCATION..ST.. ...SOURCE....\n            ..REMARKS..\n\n0358 AM     TSTM WND GST     COASTAL CAROLINA REGION 35.07N 77.04W\n04/15/2019  M61 MPH          CRAVEN             NC   ASOS             \n\n            NEW BERN/CRAVEN COUNTY ASOS (EWN) REPORTS \n            GUST OF 61 MPH. \n\n\n&&\n\n$$\n\nML\n\n","title":"Report:  61mph Wind (KMHX)","valid_ts":null,"warning":null,"watch":null}"#;
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1555316100000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KMHX","point":{"lat":35.07,"lon":-77.04},"poly":null,"county":"CRAVEN"},"md":null,"outlook":null,"report":{"reporter":"ASOS","hazard":"Wind","magnitude":61.0,"units":"Mph","was_measured":true,"report_ts":1555315080000000},"text":"NEW BERN/CRAVEN COUNTY ASOS (EWN) REPORTS GUST OF 61 MPH.","title":"Report:  61mph Wind (KMHX)","valid_ts":null,"warning":null,"watch":null,"lsr":{"id":"KMHX.1555315080000000.TSTM WND GST.COASTAL CAROLINA REGION","event_type":"ThunderstormWindGust","rating":null,"corrects":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
@@ -279,15 +816,250 @@ mod tests {
     fn parse_hail_report() {
         let product = get_product_from_file("data/products/lsr-hail-remarks");
         let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1522113360000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KSJT","point":{"lat":32.07,"lon":-100.66},"poly":null,"county":"COKE"},"md":null,"outlook":null,"report":{"reporter":"STORM CHASER","hazard":"Hail","magnitude":1.25,"units":"Inches","was_measured":false,"report_ts":1522112100000000},"text":"\n106 \nNWUS54 KSJT 270116\nLSRSJT\n\nPRELIMINARY LOCAL STORM REPORT\nNational Weather Service San Angelo Tx\n816 PM CDT MON MAR 26 2018\n\n..TIME...   ...EVENT...      ...CITY LOCATION...     ...LAT.LON...\n..DATE...   ....MAG....      ..COUNTY LOCATION..ST.. ...SOURCE....\n            ..REMARKS..\n\n0755 PM     HAIL             1 E SILVER              32.07N 100.66W\n03/26/2018  E1.25 INCH       COKE               TX   STORM CHASER    \n\n            1.25 HAIL ON HWY 208 NEAR SILVER \n\n\n&&\n\nEVENT NUMBER SJT1800032\n\n$$\n\nSJT\n\n","title":"Report:  1.25\" Hail (KSJT)","valid_ts":null,"warning":null,"watch":null}"#;
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1522113360000000,"event_type":"NwsLsr","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KSJT","point":{"lat":32.07,"lon":-100.66},"poly":null,"county":"COKE"},"md":null,"outlook":null,"report":{"reporter":"STORM CHASER","hazard":"Hail","magnitude":1.25,"units":"Inches","was_measured":false,"report_ts":1522112100000000},"text":"1.25 HAIL ON HWY 208 NEAR SILVER","title":"Report:  1.25\" Hail (KSJT)","valid_ts":null,"warning":null,"watch":null,"lsr":{"id":"KSJT.1522112100000000.HAIL.1 E SILVER","event_type":"Hail","rating":null,"corrects":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
     #[test]
-    fn parse_should_not_handle_multiple_events() {
+    fn get_skeleton_should_find_every_report() {
         let product = get_product_from_file("data/products/lsr-multiple-heavy-rain");
+        let result = get_skeleton(&product.product_text).unwrap();
+        let locations: Vec<&str> = result
+            .blocks
+            .iter()
+            .map(|x| x.top_line.get(29..53).unwrap().trim())
+            .collect();
+        assert_eq!(
+            vec!["FRANKLIN", "6 E ROY", "4 W LAVA HOT SPRINGS"],
+            locations
+        );
+    }
+
+    #[test]
+    fn get_skeleton_should_find_reports_without_remarks() {
+        let product = get_product_from_file("data/products/lsr-hail-funnel");
+        let result = get_skeleton(&product.product_text).unwrap();
+        assert_eq!(2, result.blocks.len());
+        assert!(result.blocks[1].top_line.contains("FUNNEL CLOUD"));
+    }
+
+    #[test]
+    fn parse_summary_should_ignore_the_age_threshold() {
+        let product = get_product_from_file("data/products/lsr-summary");
+        let result = parse(&product).unwrap();
+        assert_eq!(36, result.len());
+        let lsr = result[35].lsr.as_ref().unwrap();
+        assert_eq!("KBOU.1522116000000000.SNOW.2 SSW BOULDER", lsr.id);
+        assert_eq!(None, lsr.corrects);
+    }
+
+    #[test]
+    fn parse_summary_should_use_each_reports_remarks_as_its_text() {
+        let product = get_product_from_file("data/products/lsr-summary");
+        let blocks = get_skeleton(&product.product_text).unwrap().blocks;
+        let result = parse(&product).unwrap();
+        for (event, block) in result.iter().zip(blocks.iter()) {
+            assert_eq!(block.remarks, event.event.text);
+        }
+        assert!(result.iter().any(|x| x.event.text.is_some()));
+    }
+
+    #[test]
+    fn parse_corrected_report_should_read_the_original_from_the_remarks() {
+        let product = get_product_from_file("data/products/lsr-corrected-tstm-wind-dmg");
         let result = parse(&product).unwrap();
-        assert!(result.is_none());
+        let lsr = result[0].lsr.as_ref().unwrap();
+        let expected_id = "KJAN.1555193040000000.TSTM WND DMG.3 SSW VICKSBURG";
+        assert_eq!(expected_id, lsr.id);
+        assert_eq!(None, lsr.corrects);
+        let expected = Correction {
+            event: "TSTM WND DMG".to_string(),
+            city: "3 SSW VICKSBURG".to_string(),
+        };
+        assert_eq!(Some(expected), lsr.correction);
+        assert_eq!("Corrected Report:  Wind (KJAN)", result[0].event.title);
+    }
+
+    #[test]
+    fn report_log_should_find_the_original_of_a_correction() {
+        // The same report, sent in a summary with a time the correction fixes
+        let mut original = get_product_from_file("data/products/lsr-corrected-tstm-wind-dmg");
+        original.product_text = original
+            .product_text
+            .replace("REPORT...CORRECTED", "REPORT...SUMMARY")
+            .replace("0504 PM     TSTM", "0450 PM     TSTM");
+        let mut original = parse(&original).unwrap().remove(0);
+        let product = get_product_from_file("data/products/lsr-corrected-tstm-wind-dmg");
+        let mut correction = parse(&product).unwrap().remove(0);
+
+        let mut report_log = ReportLog::new();
+        assert!(report_log.insert(&mut original));
+        assert!(report_log.insert(&mut correction));
+        let lsr = correction.lsr.as_ref().unwrap();
+        let expected_id = "KJAN.1555192200000000.TSTM WND DMG.3 SSW VICKSBURG";
+        assert_eq!(expected_id, original.lsr.as_ref().unwrap().id);
+        assert_eq!(Some(expected_id.to_string()), lsr.corrects);
+    }
+
+    #[test]
+    fn report_log_should_not_guess_a_missing_original() {
+        let product = get_product_from_file("data/products/lsr-corrected-tstm-wind-dmg");
+        let mut correction = parse(&product).unwrap().remove(0);
+        let mut report_log = ReportLog::new();
+        assert!(report_log.insert(&mut correction));
+        assert_eq!(None, correction.lsr.as_ref().unwrap().corrects);
+    }
+
+    #[test]
+    fn report_log_should_drop_repeated_reports() {
+        let product = get_product_from_file("data/products/lsr-summary");
+        let mut result = parse(&product).unwrap();
+        let mut report_log = ReportLog::new();
+        let new_reports = result
+            .iter_mut()
+            .map(|x| report_log.insert(x))
+            .filter(|x| *x)
+            .count();
+        assert_eq!(35, new_reports);
+        assert!(!report_log.insert(&mut result[0]));
+    }
+
+    #[test]
+    fn report_log_should_be_reloaded() {
+        let path = env::temp_dir().join("nws-api-loader-reports.json");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let product = get_product_from_file("data/products/lsr-summary");
+        let mut result = parse(&product).unwrap();
+
+        let mut report_log = ReportLog::load(path).unwrap();
+        assert!(report_log.insert(&mut result[0]));
+        report_log.save().unwrap();
+
+        let mut report_log = ReportLog::load(path).unwrap();
+        assert!(!report_log.insert(&mut result[0]));
+        assert!(report_log.insert(&mut result[1]));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn parse_heavy_rain_report() {
+        let product = get_product_from_file("data/products/lsr-heavy-rain");
+        let result = parse(&product).unwrap();
+        let report = serde_json::to_string(&result[0].event.report).unwrap();
+        let expected = r#"{"reporter":"TRAINED SPOTTER","hazard":"Other","magnitude":2.44,"units":"Inches","was_measured":true,"report_ts":1555521000000000}"#;
+        assert_eq!(expected, report);
+        assert_eq!("Report:  2.44\" Heavy Rain (KMPX)", result[0].event.title);
+        let lsr = result[0].lsr.as_ref().unwrap();
+        assert_eq!(LsrEventType::HeavyRain, lsr.event_type);
+    }
+
+    #[test]
+    fn parse_flash_flood_report() {
+        let product = get_product_from_file("data/products/lsr-flash-flood");
+        let result = parse(&product).unwrap();
+        assert_eq!("Report:  Flood (KMPX)", result[0].event.title);
+        let lsr = result[0].lsr.as_ref().unwrap();
+        assert_eq!(LsrEventType::FlashFlood, lsr.event_type);
+    }
+
+    #[test]
+    fn get_lsr_event_type_should_cover_samples() {
+        let samples = vec![
+            ("data/products/lsr-funnel", LsrEventType::FunnelCloud),
+            ("data/products/lsr-wildfire", LsrEventType::Wildfire),
+            ("data/products/lsr-downburst", LsrEventType::Downburst),
+            (
+                "data/products/lsr-marine-wind",
+                LsrEventType::MarineThunderstormWind,
+            ),
+            (
+                "data/products/lsr-non-tstm-dmg",
+                LsrEventType::NonThunderstormWindDamage,
+            ),
+        ];
+
+        for (path, expected) in samples {
+            let product = get_product_from_file(path);
+            let skeleton = get_skeleton(&product.product_text).unwrap();
+            let raw_hazard = skeleton.blocks[0].top_line.get(12..29).unwrap().trim();
+            assert_eq!(expected, get_lsr_event_type(raw_hazard));
+        }
+    }
+
+    #[test]
+    fn get_lsr_event_type_should_keep_unknown_types() {
+        let result = get_lsr_event_type("ROTATING WALL CLD");
+        assert_eq!(LsrEventType::Other("ROTATING WALL CLD".to_string()), result);
+    }
+
+    #[test]
+    fn get_magnitude_should_convert_knots() {
+        let result = get_magnitude("M52 KTS");
+        assert_eq!(Some(60.0), result.value);
+        assert_eq!("\"Mph\"", serde_json::to_string(&result.units).unwrap());
+        assert_eq!(Some(true), result.was_measured);
+    }
+
+    #[test]
+    fn get_magnitude_should_handle_prefixes() {
+        let estimated = get_magnitude("E80 MPH");
+        assert_eq!(Some(80.0), estimated.value);
+        assert_eq!(Some(false), estimated.was_measured);
+        let unknown = get_magnitude("U1.50 INCH");
+        assert_eq!(Some(1.5), unknown.value);
+        assert_eq!(None, unknown.was_measured);
+    }
+
+    #[test]
+    fn get_magnitude_should_handle_ratings() {
+        let ef = get_magnitude("EF2");
+        assert_eq!(Some("EF2".to_string()), ef.rating);
+        assert_eq!(Some(2.0), ef.value);
+        assert!(ef.units.is_none());
+        let f = get_magnitude("F3");
+        assert_eq!(Some(3.0), f.value);
+        let unrated = get_magnitude("EFU");
+        assert_eq!(Some("EFU".to_string()), unrated.rating);
+        assert_eq!(None, unrated.value);
+    }
+
+    #[test]
+    fn get_magnitude_should_keep_values_in_other_units() {
+        let result = get_magnitude("E10 FT");
+        assert_eq!(Some(10.0), result.value);
+        assert!(result.units.is_none());
+    }
+
+    #[test]
+    fn get_skeleton_should_read_remarks_per_report() {
+        let product = get_product_from_file("data/products/lsr-hail-funnel");
+        let result = get_skeleton(&product.product_text).unwrap();
+        assert_eq!(None, result.blocks[0].remarks);
+        let expected = "OBSERVER NOTED TWO DISTINCT FUNNEL CLOUDS DURING STORM PASSAGE, THE LATTER OF WHICH WAS LARGER AND LIKELY CORRESPONDS TO THE CONFIRMED TORNADO IN THE BAKER ROAD AREA. A SECOND OBSERVER ALONG WILBEA LN ALSO NOTED THE FUNNEL CLOUD.";
+        assert_eq!(Some(expected.to_string()), result.blocks[1].remarks);
+    }
+
+    #[test]
+    fn get_remarks_should_unescape_quotes() {
+        let lines = vec![
+            "",
+            "            SNOW FELL FROM LATE AFTERNOON ONWARDS, NEVER ",
+            "            REACHING 1&QUOT;/H. ",
+            "",
+            "0140 AM     HEAVY SNOW       3 W JAMESTOWN           40.11N 105.44W",
+        ];
+        let result = get_remarks(&lines).unwrap();
+        assert_eq!(
+            "SNOW FELL FROM LATE AFTERNOON ONWARDS, NEVER REACHING 1\"/H.",
+            result
+        );
+    }
+
+    #[test]
+    fn get_remarks_blank_should_be_none() {
+        let lines = vec!["", "             ", "", "1100 PM     SNOW"];
+        assert_eq!(None, get_remarks(&lines));
     }
 }
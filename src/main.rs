@@ -4,83 +4,198 @@ extern crate serde_derive;
 extern crate slog;
 
 mod afd_parser;
+mod backfill;
+mod checkpoint;
+mod config;
+mod dead_letter;
 mod domain;
 mod ffw_parser;
+mod hazards;
 mod lsr_parser;
+#[cfg(test)]
+mod mock_api;
 mod parser;
+mod pipeline;
+mod pts_parser;
+mod registry;
+mod replay;
 mod sel_parser;
+mod sev_parser;
+mod sink;
+mod spool;
 mod svr_parser;
 mod svs_parser;
 mod swo_parser;
+mod tags;
 mod test_util;
 mod tor_parser;
+mod ugc;
 mod util;
+mod vtec;
+mod wou_parser;
 
+use self::backfill::{Backfill, BACKFILL_COMMAND};
+use self::checkpoint::{Checkpoint, CheckpointStore};
+use self::config::Config;
+use self::dead_letter::{DeadLetters, REPROCESS_COMMAND};
 use self::domain::{ListProduct, Product, ProductsResult};
-use std::sync::Arc;
+use self::hazards::Hazards;
+use self::lsr_parser::ReportLog;
+use self::pipeline::Pipeline;
+use self::registry::Registry;
+use self::replay::{Replay, REPLAY_COMMAND};
+use self::sink::Sinks;
+use slog::Logger;
+use std::env;
+use std::process;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
-use wx::util::Logger;
 
 const APP_NAME: &str = "nws_api_loader";
-const API_HOST: &str = "https://api.weather.gov";
-const POLL_INTERVAL_MS: u64 = 60_000;
-const USER_AGENT: &str = "sigtor.org";
+const COMMANDS: &[&str] = &[BACKFILL_COMMAND, REPLAY_COMMAND, REPROCESS_COMMAND];
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+// How far before the latest handled product to look for ones the API listed late
+const LATE_PRODUCT_WINDOW_MS: u64 = 30 * 60 * 1000;
 
 fn main() {
-    let logger = Logger::new(&APP_NAME);
+    process::exit(run());
+}
+
+/**
+ * Runs the loader and returns its exit code. Logging happens on another thread, so everything
+ * returns through here instead of exiting on the spot, letting the logger flush first.
+ */
+fn run() -> i32 {
+    // Usage: [config path] [backfill <product code> <start> <end> [wfo] | replay <path> [speed] |
+    // reprocess]
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let config_path = match args.first() {
+        Some(value) if !COMMANDS.contains(&value.as_str()) => args.remove(0),
+        _ => DEFAULT_CONFIG_PATH.to_string(),
+    };
+    let registry = Arc::new(Registry::new());
+    let config = match Config::load(&config_path, &registry) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("unable to load config from {}: {}", config_path, error);
+            return 1;
+        }
+    };
+
+    let (logger, _log_guard) = util::get_logger(APP_NAME, config.get_log_level());
     let mut threads = vec![];
     let logger = Arc::new(logger);
-    let product_codes = vec!["afd", "ffw", "lsr", "sel", "svr", "svs", "swo", "tor"];
-    info!(logger, "initializing"; "poll_interval_ms" => POLL_INTERVAL_MS);
+    let hazards = Arc::new(Mutex::new(Hazards::new()));
+    info!(logger, "initializing"; "config" => &config_path, "api_host" => &config.api_host);
 
-    for product_code in product_codes {
-        let logger = logger.clone();
+    match args.first().map(String::as_str) {
+        Some(BACKFILL_COMMAND) => {
+            let result = Backfill::from_args(&args[1..], &registry)
+                .and_then(|x| x.run(&config, registry.clone(), &logger));
+            match result {
+                Ok(summary) if !summary.failed_ids.is_empty() => {
+                    crit!(logger, "backfill incomplete"; "stored" => summary.stored, "failed" => summary.failed_ids.join(", "));
+                    return 1;
+                }
+                Ok(summary) => info!(logger, "backfill complete"; "stored" => summary.stored),
+                Err(error) => {
+                    crit!(logger, "backfill failed"; "error" => format!("{}", error));
+                    return 1;
+                }
+            }
+            return 0;
+        }
+        Some(REPLAY_COMMAND) => {
+            let result = Replay::from_args(&args[1..])
+                .and_then(|x| x.run(&config, registry.clone(), &logger));
+            match result {
+                Ok(stored) => info!(logger, "replay complete"; "stored" => stored),
+                Err(error) => {
+                    crit!(logger, "replay failed"; "error" => format!("{}", error));
+                    return 1;
+                }
+            }
+            return 0;
+        }
+        Some(REPROCESS_COMMAND) => {
+            match dead_letter::reprocess(&config, registry.clone(), &logger) {
+                Ok(recovered) => info!(logger, "reprocess complete"; "recovered" => recovered),
+                Err(error) => {
+                    crit!(logger, "reprocess failed"; "error" => format!("{}", error));
+                    return 1;
+                }
+            }
+            return 0;
+        }
+        Some(command) => {
+            crit!(logger, "unknown command"; "command" => command);
+            return 1;
+        }
+        None => (),
+    }
+
+    let fetcher = match util::Fetcher::new(&logger, &config.user_agent, &config.http) {
+        Ok(value) => Arc::new(value),
+        Err(error) => {
+            crit!(logger, "unable to create fetcher"; "error" => format!("{}", error));
+            return 1;
+        }
+    };
+
+    let sinks = match Sinks::from_config(&config.sinks, &config.spool) {
+        Ok(value) => Arc::new(value),
+        Err(error) => {
+            crit!(logger, "unable to create sinks"; "error" => format!("{}", error));
+            return 1;
+        }
+    };
+
+    let checkpoints = match CheckpointStore::load(&config.checkpoint_path) {
+        Ok(value) => Arc::new(Mutex::new(value)),
+        Err(error) => {
+            crit!(logger, "unable to load checkpoints"; "path" => &config.checkpoint_path, "error" => format!("{}", error));
+            return 1;
+        }
+    };
+
+    let report_log = match ReportLog::load(&config.report_log_path) {
+        Ok(value) => Arc::new(Mutex::new(value)),
+        Err(error) => {
+            crit!(logger, "unable to load report log"; "path" => &config.report_log_path, "error" => format!("{}", error));
+            return 1;
+        }
+    };
+
+    for product in &config.products {
+        let poll_interval_ms = config.get_poll_interval_ms(product, &registry);
+        let max_catch_up_ms = config.max_catch_up_ms;
+        let mut worker = Worker {
+            logger: Logger::clone(&logger),
+            fetcher: fetcher.clone(),
+            api_host: config.api_host.to_string(),
+            product_code: product.code.to_lowercase(),
+            checkpoints: checkpoints.clone(),
+            checkpoint: Checkpoint::default(),
+            hazards: hazards.clone(),
+            hazards_path: config.hazards_path.to_string(),
+            pipeline: Pipeline::new(
+                &logger,
+                registry.clone(),
+                hazards.clone(),
+                report_log.clone(),
+                sinks.clone(),
+                DeadLetters::new(&config.dead_letter_dir),
+            ),
+        };
+        info!(logger, "polling"; "product_code" => &worker.product_code, "poll_interval_ms" => poll_interval_ms);
 
         threads.push(thread::spawn(move || {
-            let client = reqwest::Client::new();
-            let store_client = wx::store::Client::new();
-            let fetcher = util::Fetcher::new(&client, &logger, USER_AGENT);
-            let mut last_product_ts = wx::util::get_system_micros();
+            worker.resume(wx::util::get_system_micros(), max_catch_up_ms);
 
             loop {
-                let url = format!("{}/products/types/{}", API_HOST, product_code);
-
-                if let Ok(product_list) = fetcher.fetch::<ProductsResult>(&url) {
-                    let products = get_new_products(last_product_ts, product_list);
-
-                    if !products.is_empty() {
-                        let new_ts = wx::util::ts_to_ticks(&products[0].issuance_time).unwrap();
-                        last_product_ts = new_ts;
-                    }
-
-                    products
-                        .iter()
-                        .map(|x| match fetcher.fetch::<Product>(&x._id) {
-                            Ok(value) => Some(value),
-                            Err(error) => {
-                                error!(logger, "Fetch error"; "error" => format!("{}", error));
-                                None
-                            }
-                        })
-                        .filter(Option::is_some)
-                        .map(|x| match parser::parse(&x.unwrap()) {
-                            Ok(value) => Some(value),
-                            Err(error) => {
-                                error!(logger, "Parsing error"; "error" => format!("{}", error));
-                                None
-                            }
-                        })
-                        .filter_map(Option::unwrap)
-                        .for_each(|x| match store_client.put_event(&x) {
-                            Ok(_) => debug!(logger, "Stored event";),
-                            Err(_) => {
-                                error!(logger, "Store error"; "error" => "unable to store event")
-                            }
-                        });
-                }
-
-                thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+                worker.poll_once(wx::util::get_system_micros());
+                thread::sleep(Duration::from_millis(poll_interval_ms));
             }
         }));
     }
@@ -88,47 +203,381 @@ fn main() {
     for thread in threads {
         let _ = thread.join();
     }
+
+    0
+}
+
+/**
+ * Polls a single product code, with the state it shares with the other workers.
+ */
+struct Worker {
+    logger: Logger,
+    fetcher: Arc<util::Fetcher>,
+    api_host: String,
+    product_code: String,
+    checkpoints: Arc<Mutex<CheckpointStore>>,
+    checkpoint: Checkpoint,
+    hazards: Arc<Mutex<Hazards>>,
+    hazards_path: String,
+    pipeline: Pipeline,
+}
+
+impl Worker {
+    /**
+     * Picks up from the saved checkpoint, catching up on at most `max_catch_up_ms` of products.
+     */
+    fn resume(&mut self, now: u64, max_catch_up_ms: u64) {
+        let saved = self.checkpoints.lock().unwrap().get(&self.product_code);
+        self.checkpoint = saved.clone().unwrap_or_default();
+        self.checkpoint.last_product_ts =
+            checkpoint::get_resume_ts(saved.as_ref(), now, max_catch_up_ms);
+        info!(self.logger, "resuming"; "product_code" => &self.product_code, "last_product_ts" => self.checkpoint.last_product_ts);
+    }
+
+    /**
+     * One pass of the polling loop: handles new products and saves the checkpoint, then retries
+     * spooled events and saves the hazards still active as of `now`. Returns how many products
+     * were handled.
+     */
+    fn poll_once(&mut self, now: u64) -> usize {
+        let pipeline = &mut self.pipeline;
+        let mut handled = 0;
+        let has_new_products = poll(
+            &self.fetcher,
+            &self.api_host,
+            &self.product_code,
+            &mut self.checkpoint,
+            |x| {
+                pipeline.process(x);
+                handled += 1;
+            },
+        );
+
+        // Only checkpoint once the products are handled, so a crash mid-poll reprocesses them
+        // on restart rather than dropping them
+        if has_new_products {
+            let result = self
+                .checkpoints
+                .lock()
+                .unwrap()
+                .save(&self.product_code, &self.checkpoint);
+            if let Err(error) = result {
+                error!(self.logger, "Checkpoint error"; "error" => format!("{}", error));
+            }
+        }
+
+        self.pipeline.retry_spooled();
+        self.pipeline.expire_hazards(now);
+        if let Err(error) = self.hazards.lock().unwrap().save(&self.hazards_path, now) {
+            error!(self.logger, "Hazards error"; "error" => format!("{}", error));
+        }
+
+        handled
+    }
 }
 
 /**
- * Returns products newer than the latest seen. A simple take_while could suffice, but that
- * carries the possibility of missing products due to an unparseable datetime string.
+ * Fetches a product code's listing and hands each new product to `handle`, marking them in the
+ * checkpoint. Products that couldn't be fetched are left unmarked, and the checkpoint doesn't
+ * move past them, so they're retried on the next poll. Returns whether any were handled, so the
+ * caller knows to save the checkpoint.
  */
-fn get_new_products(last_ts: u64, products_result: ProductsResult) -> Vec<ListProduct> {
-    let mut new_products: Vec<ListProduct> = vec![];
+fn poll<F: FnMut(&Product)>(
+    fetcher: &util::Fetcher,
+    api_host: &str,
+    product_code: &str,
+    checkpoint: &mut Checkpoint,
+    mut handle: F,
+) -> bool {
+    let url = format!("{}/products/types/{}", api_host, product_code);
+    let product_list = match fetcher.fetch_if_modified::<ProductsResult>(&url) {
+        Ok(Some(value)) => value,
+        Ok(None) => {
+            debug!(fetcher.logger, "No changes"; "url" => url);
+            return false;
+        }
+        Err(error) => {
+            error!(fetcher.logger, "Fetch error"; "error" => format!("{}", error));
+            return false;
+        }
+    };
+
+    let products = get_new_products(checkpoint, product_list);
+    let mut handled = 0;
+    // Issuance time of the oldest product that couldn't be fetched
+    let mut pending_ts: Option<u64> = None;
+
+    for product in &products {
+        let ticks = match wx::util::ts_to_ticks(&product.issuance_time) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
 
-    for product in products_result.products {
-        if let Ok(ticks) = wx::util::ts_to_ticks(&product.issuance_time) {
-            if ticks <= last_ts {
-                break;
+        match fetcher.fetch_cached::<Product>(&product._id) {
+            Ok(value) => {
+                handle(&value);
+                handled += 1;
+                checkpoint.mark_seen(&product.id, pending_ts.map_or(ticks, |x| x.min(ticks)));
+            }
+            Err(error) => {
+                error!(fetcher.logger, "Fetch error"; "error" => format!("{}", error));
+                pending_ts = Some(pending_ts.map_or(ticks, |x| x.min(ticks)));
             }
-            new_products.push(product);
         }
     }
 
-    new_products
+    // An unchanged listing would skip the pending products, so it has to be fetched in full
+    if pending_ts.is_some() {
+        fetcher.forget_validators(&url);
+    }
+
+    handled > 0
+}
+
+/**
+ * Returns the products that haven't been handled yet, oldest first. Timestamps alone aren't
+ * enough since several products can share an issuance minute and the API doesn't guarantee
+ * ordering, so anything in the checkpoint's seen IDs is skipped and unseen products issued a
+ * little before the latest one handled are still picked up. A checkpoint without any IDs has
+ * nothing to compare against, so it only takes products issued after its timestamp. Products
+ * with an unparseable datetime string are skipped.
+ */
+fn get_new_products(checkpoint: &Checkpoint, products_result: ProductsResult) -> Vec<ListProduct> {
+    let earliest_ts = if checkpoint.seen_ids.is_empty() {
+        checkpoint.last_product_ts + 1
+    } else {
+        checkpoint
+            .last_product_ts
+            .saturating_sub(LATE_PRODUCT_WINDOW_MS * 1000)
+    };
+
+    let mut new_products: Vec<(u64, ListProduct)> = products_result
+        .products
+        .into_iter()
+        .filter(|x| !checkpoint.has_seen(&x.id))
+        .filter_map(|x| match wx::util::ts_to_ticks(&x.issuance_time) {
+            Ok(ticks) if ticks >= earliest_ts => Some((ticks, x)),
+            _ => None,
+        })
+        .collect();
+
+    new_products.sort_by_key(|x| x.0);
+    new_products.into_iter().map(|x| x.1).collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use super::config::{HttpConfig, SinkConfig, SpoolConfig};
+    use super::mock_api::{Fault, MockApi};
     use super::*;
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::Read;
+    use std::path::Path;
 
-    #[test]
-    fn get_new_products_should_only_parse_newer_products() {
-        let last_ts = 1555977060000000;
+    fn get_product_list() -> ProductsResult {
         let mut f = File::open("data/product-list-tor").expect("product file not found");
         let mut contents = String::new();
         f.read_to_string(&mut contents)
             .expect("something went wrong reading the file");
-        let result: ProductsResult = serde_json::from_str(&contents).unwrap();
-        let new_products = get_new_products(last_ts, result);
-        let ids: Vec<&str> = new_products.iter().map(|x| x.id.as_str()).collect();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    fn get_checkpoint(last_product_ts: u64, seen: &[&str]) -> Checkpoint {
+        Checkpoint {
+            last_product_ts,
+            seen_ids: seen
+                .iter()
+                .map(|x| format!("e0fdf7de-6229-4330-9d4d-3a2af96ffa{}", x))
+                .collect(),
+        }
+    }
+
+    fn get_ids(products: &[ListProduct]) -> Vec<&str> {
+        products.iter().map(|x| x.id.as_str()).collect()
+    }
+
+    #[test]
+    fn get_new_products_should_only_parse_newer_products() {
+        let checkpoint = get_checkpoint(1555977060000000, &[]);
+        let new_products = get_new_products(&checkpoint, get_product_list());
         let expected_ids = vec![
-            "e0fdf7de-6229-4330-9d4d-3a2af96ffa4c",
             "e0fdf7de-6229-4330-9d4d-3a2af96ffa4d",
+            "e0fdf7de-6229-4330-9d4d-3a2af96ffa4c",
         ];
-        assert_eq!(expected_ids, ids);
+        assert_eq!(expected_ids, get_ids(&new_products));
+    }
+
+    #[test]
+    fn get_new_products_should_keep_products_issued_the_same_minute() {
+        let mut product_list = get_product_list();
+        product_list.products[0].issuance_time = "2019-04-22T23:52:00+00:00".to_string();
+        let checkpoint = get_checkpoint(1555977120000000, &["4d", "4e", "4f"]);
+        let new_products = get_new_products(&checkpoint, product_list);
+        let expected_ids = vec!["e0fdf7de-6229-4330-9d4d-3a2af96ffa4c"];
+        assert_eq!(expected_ids, get_ids(&new_products));
+    }
+
+    #[test]
+    fn get_new_products_should_keep_late_products() {
+        let checkpoint = get_checkpoint(1555977180000000, &["4c", "4d", "4f"]);
+        let new_products = get_new_products(&checkpoint, get_product_list());
+        let expected_ids = vec!["e0fdf7de-6229-4330-9d4d-3a2af96ffa4e"];
+        assert_eq!(expected_ids, get_ids(&new_products));
+    }
+
+    #[test]
+    fn get_new_products_should_skip_seen_products() {
+        let checkpoint = get_checkpoint(1555977180000000, &["4c", "4d", "4e", "4f"]);
+        let new_products = get_new_products(&checkpoint, get_product_list());
+        assert!(new_products.is_empty());
+    }
+
+    fn get_fetcher() -> util::Fetcher {
+        test_util::get_fetcher(&HttpConfig {
+            min_request_interval_ms: 0,
+            ..HttpConfig::default()
+        })
+    }
+
+    #[test]
+    fn poll_should_handle_released_products_once() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher();
+        let mut checkpoint = Checkpoint::default();
+        let mut handled: Vec<String> = vec![];
+
+        for _ in 0..10 {
+            api.release(8);
+            poll(&fetcher, api.host(), "tor", &mut checkpoint, |x| {
+                handled.push(x.id.to_string())
+            });
+        }
+
+        assert_eq!(9, handled.len());
+        assert!(!poll(&fetcher, api.host(), "tor", &mut checkpoint, |_| {
+            panic!("no new products")
+        }));
+        handled.sort();
+        handled.dedup();
+        assert_eq!(9, handled.len());
+    }
+
+    #[test]
+    fn poll_should_retry_products_after_a_failed_listing() {
+        let api = MockApi::from_dir("data/products");
+        api.release_all();
+        let fetcher = get_fetcher();
+        let mut checkpoint = Checkpoint::default();
+        let mut handled = 0;
+
+        api.inject(Fault::Status(404));
+        assert!(!poll(&fetcher, api.host(), "svr", &mut checkpoint, |_| {
+            handled += 1
+        }));
+        assert!(poll(&fetcher, api.host(), "svr", &mut checkpoint, |_| {
+            handled += 1
+        }));
+        assert_eq!(2, handled);
+    }
+
+    #[test]
+    fn poll_should_retry_products_that_failed_to_fetch() {
+        let api = MockApi::from_dir("data/products");
+        api.release_all();
+        let fetcher = get_fetcher();
+        let mut checkpoint = Checkpoint::default();
+        let mut handled: Vec<String> = vec![];
+
+        // The listing is served normally, then the oldest product fails
+        api.inject(Fault::Delay(Duration::from_millis(0)));
+        api.inject(Fault::Status(404));
+        assert!(poll(&fetcher, api.host(), "tor", &mut checkpoint, |x| {
+            handled.push(x.id.to_string())
+        }));
+        assert_eq!(8, handled.len());
+
+        // It's the oldest, so the checkpoint shouldn't have moved past it
+        let url = format!("{}/products/types/tor", api.host());
+        let oldest_ts = fetcher
+            .fetch::<ProductsResult>(&url)
+            .unwrap()
+            .products
+            .iter()
+            .map(|x| wx::util::ts_to_ticks(&x.issuance_time).unwrap())
+            .min();
+        assert_eq!(oldest_ts, Some(checkpoint.last_product_ts));
+
+        assert!(poll(&fetcher, api.host(), "tor", &mut checkpoint, |x| {
+            handled.push(x.id.to_string())
+        }));
+        assert_eq!(9, handled.len());
+        assert!(!handled[..8].contains(&handled[8]));
+    }
+
+    fn get_worker(api_host: &str, dir: &Path) -> Worker {
+        let logger = test_util::get_logger();
+        let get_path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let sink_config = SinkConfig::File {
+            path: get_path("events.jsonl"),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 1,
+        };
+        let spool_config = SpoolConfig {
+            dir: get_path("spool"),
+            ..SpoolConfig::default()
+        };
+        let sinks = Sinks::from_config(&[sink_config], &spool_config).unwrap();
+        let checkpoints = CheckpointStore::load(&get_path("checkpoints.json")).unwrap();
+        let hazards = Arc::new(Mutex::new(Hazards::new()));
+
+        Worker {
+            logger: logger.clone(),
+            fetcher: Arc::new(get_fetcher()),
+            api_host: api_host.to_string(),
+            product_code: "tor".to_string(),
+            checkpoints: Arc::new(Mutex::new(checkpoints)),
+            checkpoint: Checkpoint::default(),
+            hazards: hazards.clone(),
+            hazards_path: get_path("hazards.json"),
+            pipeline: Pipeline::new(
+                &logger,
+                Arc::new(Registry::new()),
+                hazards,
+                Arc::new(Mutex::new(ReportLog::new())),
+                Arc::new(sinks),
+                DeadLetters::new(&get_path("dead-letters")),
+            ),
+        }
+    }
+
+    #[test]
+    fn poll_once_should_resume_after_a_restart() {
+        let dir = env::temp_dir().join("nws-api-loader-restart");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let api = MockApi::from_dir("data/products");
+        let max_catch_up_ms = 6 * 60 * 60 * 1000;
+
+        // Without a checkpoint it starts from `now`, so start before the samples were issued
+        api.release(30);
+        let mut worker = get_worker(api.host(), &dir);
+        worker.resume(0, max_catch_up_ms);
+        let handled_before = worker.poll_once(0);
+        let checkpoint = worker.checkpoint.clone();
+        assert!(handled_before > 0);
+        assert!(dir.join("hazards.json").exists());
+        drop(worker);
+
+        api.release_all();
+        let mut worker = get_worker(api.host(), &dir);
+        worker.resume(checkpoint.last_product_ts, max_catch_up_ms);
+        assert_eq!(checkpoint, worker.checkpoint);
+        let handled_after = worker.poll_once(checkpoint.last_product_ts);
+
+        // Every TOR is handled once across the restart
+        assert!(handled_after > 0);
+        assert_eq!(9, handled_before + handled_after);
+        assert_eq!(0, worker.poll_once(checkpoint.last_product_ts));
+        let _ = fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,389 @@
+use super::domain::Product;
+use super::replay;
+use super::test_util::get_logger;
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/**
+ * A failure to return instead of the next response.
+ */
+#[derive(Clone, Debug)]
+pub enum Fault {
+    Status(u16),
+    // A 429 with a Retry-After in seconds
+    TooManyRequests(u64),
+    // Waits before responding normally, to exercise client timeouts
+    Delay(Duration),
+    MalformedJson,
+}
+
+struct State {
+    // Oldest first, only the first `released` are listed
+    products: Vec<(u64, Product)>,
+    released: usize,
+    faults: VecDeque<Fault>,
+    request_count: usize,
+}
+
+/**
+ * A stand-in for api.weather.gov that serves `/products/types/{code}` listings and
+ * `/products/{id}` documents on a local port, so fetching and polling can be tested end to
+ * end. Products start out hidden and are released over time, oldest first, as if they were
+ * being issued.
+ */
+pub struct MockApi {
+    host: String,
+    state: Arc<Mutex<State>>,
+    is_stopped: Arc<AtomicBool>,
+}
+
+impl MockApi {
+    pub fn start(mut products: Vec<(u64, Product)>) -> MockApi {
+        // Some sample products are copies of each other, which the API wouldn't list twice
+        let mut ids = HashSet::new();
+        products.retain(|x| ids.insert(x.1.id.to_string()));
+        products.sort_by_key(|x| x.0);
+        let listener = TcpListener::bind("127.0.0.1:0").expect("unable to bind mock api");
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State {
+            products,
+            released: 0,
+            faults: VecDeque::new(),
+            request_count: 0,
+        }));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+
+        {
+            let host = host.to_string();
+            let state = state.clone();
+            let is_stopped = is_stopped.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if is_stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        handle(stream, &host, &state);
+                    }
+                }
+            });
+        }
+
+        MockApi {
+            host,
+            state,
+            is_stopped,
+        }
+    }
+
+    /**
+     * Serves every product in a directory, like `data/products`.
+     */
+    pub fn from_dir(path: &str) -> MockApi {
+        let products = replay::load_products(path, &get_logger()).expect("unable to load products");
+        MockApi::start(products)
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /**
+     * Lists the next `count` products.
+     */
+    pub fn release(&self, count: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.released = (state.released + count).min(state.products.len());
+    }
+
+    pub fn release_all(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.released = state.products.len();
+    }
+
+    /**
+     * Queues a fault for the next request that doesn't already have one.
+     */
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.push_back(fault);
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.state.lock().unwrap().request_count
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::SeqCst);
+        // Wake the listener up so it sees the flag
+        let _ = TcpStream::connect(self.host.trim_start_matches("http://"));
+    }
+}
+
+fn handle(mut stream: TcpStream, host: &str, state: &Arc<Mutex<State>>) {
+    let mut request_line = String::new();
+    let mut if_none_match = None;
+    {
+        let mut reader = BufReader::new(&stream);
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+        // Headers have to be read before responding, even if they aren't needed
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap_or(0) > 2 {
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or("").trim();
+            if name.eq_ignore_ascii_case("if-none-match") {
+                if_none_match = header.next().map(|x| x.trim().to_string());
+            }
+            line.clear();
+        }
+    }
+
+    let path = request_line.split(' ').nth(1).unwrap_or("").to_string();
+    let (fault, response) = {
+        let mut state = state.lock().unwrap();
+        state.request_count += 1;
+        (state.faults.pop_front(), get_response(&path, host, &state))
+    };
+
+    let mut headers = String::new();
+    let (status, body) = match fault {
+        Some(Fault::Status(status)) => (status, "{}".to_string()),
+        Some(Fault::TooManyRequests(seconds)) => {
+            headers = format!("Retry-After: {}\r\n", seconds);
+            (429, "{}".to_string())
+        }
+        Some(Fault::MalformedJson) => (200, "{\"@graph\": [".to_string()),
+        Some(Fault::Delay(duration)) => {
+            thread::sleep(duration);
+            response
+        }
+        None => response,
+    };
+
+    // Every response gets an ETag, so conditional requests can be told nothing changed
+    let (status, body) = if status == 200 {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        let etag = format!("\"{:x}\"", hasher.finish());
+        headers.push_str(&format!("ETag: {}\r\n", etag));
+        if if_none_match.as_ref() == Some(&etag) {
+            (304, String::new())
+        } else {
+            (status, body)
+        }
+    } else {
+        (status, body)
+    };
+
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/ld+json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+        status,
+        match status {
+            200 => "OK",
+            304 => "Not Modified",
+            _ => "Error",
+        },
+        body.len(),
+        headers,
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn get_response(path: &str, host: &str, state: &State) -> (u16, String) {
+    let released = &state.products[..state.released];
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        ["products", "types", code] => {
+            // Listings are newest first, like the API
+            let products: Vec<serde_json::Value> = released
+                .iter()
+                .rev()
+                .map(|x| &x.1)
+                .filter(|x| x.product_code.eq_ignore_ascii_case(code))
+                .map(|x| {
+                    json!({
+                        "@id": format!("{}/products/{}", host, x.id),
+                        "id": x.id,
+                        "wmoCollectiveId": x.wmo_collective_id,
+                        "issuingOffice": x.issuing_office,
+                        "issuanceTime": x.issuance_time,
+                        "productCode": x.product_code,
+                        "productName": x.product_name,
+                    })
+                })
+                .collect();
+            let body = json!({
+                "@context": {"@vocab": "https://api.weather.gov/ontology#"},
+                "@graph": products,
+            });
+            (200, body.to_string())
+        }
+        ["products", id] => match released.iter().find(|x| x.1.id == *id) {
+            Some((_, product)) => {
+                let mut body = serde_json::to_value(product).unwrap();
+                body["@id"] = json!(format!("{}/products/{}", host, id));
+                (200, body.to_string())
+            }
+            None => (404, "{}".to_string()),
+        },
+        _ => (404, "{}".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::config::HttpConfig;
+    use super::super::domain::ProductsResult;
+    use super::super::test_util::get_fetcher;
+    use super::*;
+    use std::time::Instant;
+
+    fn get_test_config() -> HttpConfig {
+        HttpConfig {
+            backoff_base_ms: 10,
+            min_request_interval_ms: 0,
+            ..HttpConfig::default()
+        }
+    }
+
+    #[test]
+    fn listing_should_only_include_released_products() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!("{}/products/types/tor", api.host());
+
+        let result = fetcher.fetch::<ProductsResult>(&url).unwrap();
+        assert!(result.products.is_empty());
+
+        api.release_all();
+        let result = fetcher.fetch::<ProductsResult>(&url).unwrap();
+        assert!(!result.products.is_empty());
+        assert!(result.products.iter().all(|x| x.product_code == "TOR"));
+
+        let product = fetcher.fetch::<Product>(&result.products[0]._id).unwrap();
+        assert_eq!(result.products[0].id, product.id);
+        assert_eq!(result.products[0].issuance_time, product.issuance_time);
+    }
+
+    #[test]
+    fn fetch_should_retry_server_errors() {
+        let api = MockApi::from_dir("data/products");
+        api.release_all();
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!("{}/products/types/svr", api.host());
+
+        api.inject(Fault::Status(503));
+        api.inject(Fault::Status(502));
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_ok());
+        api.inject(Fault::MalformedJson);
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_err());
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_ok());
+    }
+
+    #[test]
+    fn fetch_should_give_up_after_max_retries() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&HttpConfig {
+            max_retries: 1,
+            ..get_test_config()
+        });
+        let url = format!("{}/products/types/svr", api.host());
+        api.inject(Fault::Status(500));
+        api.inject(Fault::Status(500));
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_err());
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_ok());
+    }
+
+    #[test]
+    fn fetch_should_honor_retry_after() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!("{}/products/types/svr", api.host());
+        let start = Instant::now();
+        api.inject(Fault::TooManyRequests(1));
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_ok());
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn fetch_should_clamp_long_retry_afters() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&HttpConfig {
+            backoff_max_ms: 100,
+            ..get_test_config()
+        });
+        let url = format!("{}/products/types/svr", api.host());
+        let start = Instant::now();
+        api.inject(Fault::TooManyRequests(3_600));
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_ok());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn fetch_should_time_out_slow_responses() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&HttpConfig {
+            request_timeout_ms: 100,
+            max_retries: 0,
+            ..get_test_config()
+        });
+        let url = format!("{}/products/types/svr", api.host());
+        api.inject(Fault::Delay(Duration::from_millis(500)));
+        assert!(fetcher.fetch::<ProductsResult>(&url).is_err());
+    }
+
+    #[test]
+    fn fetch_if_modified_should_skip_unchanged_listings() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!("{}/products/types/tor", api.host());
+
+        let result = fetcher.fetch_if_modified::<ProductsResult>(&url).unwrap();
+        assert!(result.unwrap().products.is_empty());
+        assert!(fetcher
+            .fetch_if_modified::<ProductsResult>(&url)
+            .unwrap()
+            .is_none());
+
+        api.release_all();
+        let result = fetcher.fetch_if_modified::<ProductsResult>(&url).unwrap();
+        assert!(!result.unwrap().products.is_empty());
+    }
+
+    #[test]
+    fn fetch_cached_should_only_request_once() {
+        let api = MockApi::from_dir("data/products");
+        api.release_all();
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!(
+            "{}/products/e0fdf7de-6229-4330-9d4d-3a2af96ffa4c",
+            api.host()
+        );
+
+        let first = fetcher.fetch_cached::<Product>(&url).unwrap();
+        let second = fetcher.fetch_cached::<Product>(&url).unwrap();
+        assert_eq!(first.product_text, second.product_text);
+        assert_eq!(1, api.request_count());
+    }
+
+    #[test]
+    fn unknown_products_should_not_be_found() {
+        let api = MockApi::from_dir("data/products");
+        let fetcher = get_fetcher(&get_test_config());
+        let url = format!("{}/products/not-a-product", api.host());
+        assert!(fetcher.fetch::<Product>(&url).is_err());
+    }
+}
@@ -1,47 +1,47 @@
-use super::afd_parser;
 use super::domain::Product;
-use super::ffw_parser;
-use super::lsr_parser;
-use super::sel_parser;
-use super::svr_parser;
-use super::svs_parser;
-use super::swo_parser;
-use super::tor_parser;
 use chrono::prelude::*;
-use regex::{Match, Regex, RegexBuilder};
-use std::panic;
-use wx::domain::Event;
-use wx::error::{Error, WxError};
+use regex::{Captures, Regex, RegexBuilder};
+use std::fmt;
+use std::str::FromStr;
+use wx::domain::Coordinates;
+use wx::util;
 
 pub struct Regexes {
     pub movement: Regex,
     pub poly_condensed: Regex,
     pub source: Regex,
-    pub valid: Regex,
     pub affected: Regex,
     pub probability: Regex,
     pub wfos: Regex,
     pub md_number: Regex,
     pub watch_id: Regex,
-    pub poly: Regex,
     pub warning_for: Regex,
     pub watch_for: Regex,
+    pub sev_watch: Regex,
+    pub ugc: Regex,
+    pub vtec: Regex,
+    pub lat_lon: Regex,
+    pub tag: Regex,
 }
 
 impl Regexes {
     pub fn new() -> Regexes {
         let movement_pattern = r"\ntime...mot...loc\s(?P<time>\d{4}z)\s(?P<deg>\d+)\D{3}\s(?P<kt>\d+)kt\s(?P<lat>\d{4})\s(?P<lon>\d{4,5})";
         let source_pattern = r"\n{2}\s{2}source...(?P<src>[\s|\S]*?)\.";
-        let valid_pattern = r"(\d{6}t\d{4}z)-(\d{6}t\d{4}z)";
-        let affected_pattern = r"Areas affected\.{3}([\S|\s]*?)\n\n";
-        let probability_pattern = r"Probability of Watch Issuance...(\d{1,3}) percent";
-        let wfos_pattern = r"ATTN...WFO...([\s|\S]*?)\n\n";
-        let poly_pattern = r"(\d{4}\s\d{4,5})+";
-        let poly_condensed_pattern = r"(\d{8})\s";
-        let md_number_pattern = r"Mesoscale Discussion (\d{4})";
-        let watch_id_pattern = r"Watch Number (\d{1,3})";
+        let affected_pattern = r"Areas affected\.{3}(?P<affected>[\S|\s]*?)\n\n";
+        let probability_pattern =
+            r"Probability of Watch Issuance...(?P<probability>\d{1,3}) percent";
+        let wfos_pattern = r"ATTN...WFO...(?P<wfos>[\s|\S]*?)\n\n";
+        let poly_condensed_pattern = r"(?P<point>\d{8})\s";
+        let md_number_pattern = r"Mesoscale Discussion (?P<id>\d{4})";
+        let watch_id_pattern = r"Watch Number (?P<id>\d{1,3})";
         let warning_for_pattern = r"Warning for...([\s|\S]+?)\n\n";
         let watch_for_pattern = r"Watch for portions of\s\n([\s|\S]+?)\n\n";
+        let sev_watch_pattern = r"SEVR\s(?P<date>\d{6})\s(?P<start>\d{4})\s(?P<type>W[ST])(?P<id>\d{4})\s(?P<end>\d{4})\n(?P<points>[\d\.\s]+?);";
+        let ugc_pattern = r"(?m)^[A-Z]{2}[CZ]\d{3}[\w\->\s]*?\d{6}-";
+        let vtec_pattern = r"/(?P<class>[OTEX])\.(?P<action>[A-Z]{3})\.(?P<office>[A-Z]{4})\.(?P<phenomena>[A-Z]{2})\.(?P<significance>[A-Z])\.(?P<etn>\d{4})\.(?P<begin>\d{6}T\d{4}Z)-(?P<end>\d{6}T\d{4}Z)/";
+        let lat_lon_pattern = r"LAT\.{3}LON(?P<points>(\s+\d{4,5})+)";
+        let tag_pattern = r"(?m)^(?P<key>[A-Z][A-Z ]*?)\.{3}(?P<value>[^\n]+?)[ \t]*$";
 
         Regexes {
             movement: RegexBuilder::new(movement_pattern)
@@ -56,10 +56,6 @@ impl Regexes {
                 .case_insensitive(true)
                 .build()
                 .unwrap(),
-            valid: RegexBuilder::new(valid_pattern)
-                .case_insensitive(true)
-                .build()
-                .unwrap(),
             affected: RegexBuilder::new(affected_pattern)
                 .case_insensitive(true)
                 .build()
@@ -72,10 +68,6 @@ impl Regexes {
                 .case_insensitive(true)
                 .build()
                 .unwrap(),
-            poly: RegexBuilder::new(poly_pattern)
-                .case_insensitive(true)
-                .build()
-                .unwrap(),
             md_number: RegexBuilder::new(md_number_pattern)
                 .case_insensitive(true)
                 .build()
@@ -92,64 +84,325 @@ impl Regexes {
                 .case_insensitive(true)
                 .build()
                 .unwrap(),
+            sev_watch: RegexBuilder::new(sev_watch_pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
+            ugc: RegexBuilder::new(ugc_pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
+            vtec: RegexBuilder::new(vtec_pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
+            lat_lon: RegexBuilder::new(lat_lon_pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
+            tag: RegexBuilder::new(tag_pattern)
+                .case_insensitive(true)
+                .build()
+                .unwrap(),
         }
     }
 }
 
 /**
- * Function that determines which product gets which parser.
- * NOTE: We're forcing no abort panics in the Cargo.toml, and catching panics here.
- * Yes, this is UGLY and BAD - but there are a ton of slices and regex Option unwraps
- * buried within. One rainy day, I'll start validating the correctness, but for now,
- * it's critical that the processing threads don't die.
+ * Why a parser couldn't read a product: a field it expected was missing, or had a value it
+ * couldn't make sense of. The offset is where in the product text the field was, when known.
  */
-pub fn parse(product: &Product) -> Result<Option<Event>, Error> {
-    let regexes = Regexes::new();
-
-    let result = panic::catch_unwind(|| match product.product_code.as_ref() {
-        "AFD" => afd_parser::parse(&product),
-        "LSR" => lsr_parser::parse(&product),
-        "SEL" => sel_parser::parse(&product, regexes),
-        "SVR" => svr_parser::parse(&product, regexes),
-        "SVS" => svs_parser::parse(&product),
-        "SWO" => swo_parser::parse(&product, regexes),
-        "TOR" => tor_parser::parse(&product, regexes),
-        "FFW" => ffw_parser::parse(&product, regexes),
-        _ => {
-            let reason = format!("unknown product code: {}", &product.product_code);
-            Err(Error::Wx(<WxError>::new(&reason)))
+#[derive(Debug, PartialEq)]
+pub enum FieldError {
+    Missing {
+        field: &'static str,
+        offset: Option<usize>,
+    },
+    Invalid {
+        field: &'static str,
+        value: String,
+        offset: Option<usize>,
+    },
+}
+
+impl FieldError {
+    pub fn missing(field: &'static str) -> FieldError {
+        FieldError::Missing {
+            field,
+            offset: None,
         }
-    });
+    }
+
+    pub fn invalid(field: &'static str, value: &str) -> FieldError {
+        FieldError::Invalid {
+            field,
+            value: value.to_string(),
+            offset: None,
+        }
+    }
+
+    pub fn at(self, offset: Option<usize>) -> FieldError {
+        match self {
+            FieldError::Missing { field, .. } => FieldError::Missing { field, offset },
+            FieldError::Invalid { field, value, .. } => FieldError::Invalid {
+                field,
+                value,
+                offset,
+            },
+        }
+    }
+
+    /**
+     * Parsers that work on part of a product (ex. a UGC segment) find offsets within that part,
+     * so this moves them to be within the whole text.
+     */
+    pub fn within(self, text: &str, part: &str) -> FieldError {
+        let start = get_offset(text, part).unwrap_or(0);
+        let offset = match &self {
+            FieldError::Missing { offset, .. } | FieldError::Invalid { offset, .. } => {
+                offset.map(|x| x + start)
+            }
+        };
+        self.at(offset)
+    }
+}
+
+/**
+ * A product that couldn't be parsed, with enough detail to find the problem in its text.
+ */
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnsupportedProduct {
+        product_id: String,
+        product_code: String,
+    },
+    MissingField {
+        product_id: String,
+        product_code: String,
+        field: &'static str,
+        offset: Option<usize>,
+    },
+    InvalidField {
+        product_id: String,
+        product_code: String,
+        field: &'static str,
+        value: String,
+        offset: Option<usize>,
+    },
+}
+
+impl ParseError {
+    pub fn new(product: &Product, error: FieldError) -> ParseError {
+        let product_id = product.id.to_string();
+        let product_code = product.product_code.to_string();
+
+        match error {
+            FieldError::Missing { field, offset } => ParseError::MissingField {
+                product_id,
+                product_code,
+                field,
+                offset,
+            },
+            FieldError::Invalid {
+                field,
+                value,
+                offset,
+            } => ParseError::InvalidField {
+                product_id,
+                product_code,
+                field,
+                value,
+                offset,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnsupportedProduct {
+                product_id,
+                product_code,
+            } => write!(
+                f,
+                "unsupported product code {} in {}",
+                product_code, product_id
+            ),
+            ParseError::MissingField {
+                product_id,
+                product_code,
+                field,
+                offset,
+            } => write!(
+                f,
+                "missing {} in {} {}{}",
+                field,
+                product_code,
+                product_id,
+                get_offset_text(*offset)
+            ),
+            ParseError::InvalidField {
+                product_id,
+                product_code,
+                field,
+                value,
+                offset,
+            } => write!(
+                f,
+                "invalid {} {:?} in {} {}{}",
+                field,
+                value,
+                product_code,
+                product_id,
+                get_offset_text(*offset)
+            ),
+        }
+    }
+}
+
+// ex. ` at offset 1238`
+fn get_offset_text(offset: Option<usize>) -> String {
+    offset.map_or_else(String::new, |x| format!(" at offset {}", x))
+}
+
+impl std::error::Error for ParseError {}
 
-    if result.is_ok() {
-        result.unwrap()
+pub fn get_issuance_ticks(product: &Product) -> Result<u64, FieldError> {
+    util::ts_to_ticks(&product.issuance_time)
+        .map_err(|_| FieldError::invalid("issuance time", &product.issuance_time))
+}
+
+pub fn short_time_to_ticks(input: &str) -> Result<u64, FieldError> {
+    match Utc.datetime_from_str(input, "%y%m%dT%H%MZ") {
+        Ok(value) => Ok(value.timestamp() as u64 * 1_000_000),
+        Err(_) => Err(FieldError::invalid("time", input)),
+    }
+}
+
+// Returns where `part` starts in `text`, if it's a slice of it
+pub fn get_offset(text: &str, part: &str) -> Option<usize> {
+    let start = text.as_ptr() as usize;
+    let part_start = part.as_ptr() as usize;
+
+    if part_start >= start && part_start + part.len() <= start + text.len() {
+        Some(part_start - start)
     } else {
-        let reason = format!("recovered from panic on product: {}", product.id);
-        Err(Error::Wx(<WxError>::new(&reason)))
+        None
+    }
+}
+
+/**
+ * Returns a named capture group. Fields are named after their group, and offsets are within
+ * the text the captures came from.
+ */
+pub fn cap<'t>(captures: &Captures<'t>, name: &'static str) -> Result<&'t str, FieldError> {
+    match captures.name(name) {
+        Some(value) => Ok(value.as_str()),
+        None => Err(FieldError::missing(name).at(captures.get(0).map(|x| x.start()))),
+    }
+}
+
+// Like `cap`, but also parses the group's value, ex. a motion in knots
+pub fn parse_cap<T: FromStr>(captures: &Captures, name: &'static str) -> Result<T, FieldError> {
+    cap(captures, name)?
+        .parse::<T>()
+        .map_err(|_| get_invalid(captures, name))
+}
+
+// For a group that was captured but whose value doesn't make sense
+pub fn get_invalid(captures: &Captures, name: &'static str) -> FieldError {
+    match captures.name(name) {
+        Some(value) => FieldError::invalid(name, value.as_str()).at(Some(value.start())),
+        None => FieldError::missing(name),
     }
 }
 
-pub fn short_time_to_ticks(input: &str) -> Result<u64, Error> {
-    Ok(Utc.datetime_from_str(input, "%y%m%dT%H%MZ")?.timestamp() as u64 * 1_000_000)
+/**
+ * Returns the area a warning was issued for, from the `Warning for...` paragraph with its
+ * wrapping and list separators removed, ex. `Northwestern Pulaski County in Arkansas`.
+ */
+pub fn get_issued_for(text: &str, regexes: &Regexes) -> Result<String, FieldError> {
+    let captures = regexes
+        .warning_for
+        .captures(text)
+        .ok_or_else(|| FieldError::missing("warning for"))?;
+    let raw_issued_for = captures
+        .get(1)
+        .ok_or_else(|| FieldError::missing("warning for"))?;
+    let issued_for = raw_issued_for
+        .as_str()
+        .replace("\n", "")
+        .replace("...", ",")
+        .replace("  ", " ");
+    let mut issued_for = issued_for.trim().to_string();
+    // Drop the trailing separator
+    issued_for.pop();
+    Ok(issued_for)
+}
+
+/**
+ * Returns the vertices in a product's LAT...LON block, which can wrap onto indented lines.
+ * Products leave the ring open, so the first vertex is repeated at the end to close it.
+ */
+pub fn get_poly(text: &str, regexes: &Regexes) -> Result<Vec<Coordinates>, FieldError> {
+    let points: Vec<&str> = match regexes.lat_lon.captures(text) {
+        Some(captures) => cap(&captures, "points")?.split_whitespace().collect(),
+        None => return Ok(vec![]),
+    };
+
+    let mut poly = vec![];
+    for point in points.chunks(2) {
+        if let [lat, lon] = point {
+            poly.push(Coordinates {
+                lat: str_to_latlon(lat, false).map_err(|x| x.at(get_offset(text, lat)))?,
+                lon: str_to_latlon(lon, true).map_err(|x| x.at(get_offset(text, lon)))?,
+            });
+        }
+    }
+
+    close_ring(&mut poly);
+    Ok(poly)
 }
 
-pub fn get_parse_error(text: &str) -> Error {
-    let reason = format!("unable to parse product: {}", text);
-    Error::Wx(<WxError>::new(&reason))
+/**
+ * Repeats the first vertex at the end of a polygon if it isn't there already.
+ */
+pub fn close_ring(poly: &mut Vec<Coordinates>) {
+    if let (Some(first), Some(last)) = (poly.first(), poly.last()) {
+        if first.lat != last.lat || first.lon != last.lon {
+            let closing = Coordinates {
+                lat: first.lat,
+                lon: first.lon,
+            };
+            poly.push(closing);
+        }
+    }
 }
 
-pub fn cap(m: Option<Match>) -> &str {
-    m.unwrap().as_str()
+/**
+ * Outlook and MD polygons condense each vertex into 8 digits, ex. `35460123` is 35.46, -101.23.
+ */
+pub fn get_condensed_coordinates(input: &str) -> Result<Coordinates, FieldError> {
+    match (input.get(0..4), input.get(4..8)) {
+        (Some(lat), Some(lon)) if input.len() == 8 => Ok(Coordinates {
+            lat: str_to_latlon(lat, false)?,
+            lon: str_to_latlon(lon, true)?,
+        }),
+        _ => Err(FieldError::invalid("point", input)),
+    }
 }
 
-pub fn str_to_latlon(input: &str, invert: bool) -> f32 {
-    let sign = if invert { -1.0 } else { 1.0 };
-    let mut value = input.parse::<f32>().unwrap();
+pub fn str_to_latlon(input: &str, invert: bool) -> Result<f32, FieldError> {
+    let (field, sign) = if invert { ("lon", -1.0) } else { ("lat", 1.0) };
+    let mut value = input
+        .parse::<f32>()
+        .map_err(|_| FieldError::invalid(field, input))?;
     // longitudes are inverted, and values over 100 can drop the '1'
     if invert && value < 5000.0 {
         value += 10000.0;
     }
-    value / 100.0 * sign
+    Ok(value / 100.0 * sign)
 }
 
 #[cfg(test)]
@@ -169,15 +422,59 @@ mod tests {
         ];
 
         tests.iter().for_each(|x| {
-            let result = str_to_latlon(x.0, x.1);
+            let result = str_to_latlon(x.0, x.1).unwrap();
             assert_eq!(x.2, result);
         });
     }
 
+    #[test]
+    fn get_poly_should_include_continuation_lines() {
+        let text = "LAT...LON 3642 7666 3670 7661 3667 7631 3649 7647\n      3651 7649 3647 7649 3641 7654\nTIME...MOT...LOC 0026Z 196DEG 43KT 3646 7659 \n";
+        let regexes = Regexes::new();
+        let result = get_poly(text, &regexes).unwrap();
+        assert_eq!(8, result.len());
+        assert_eq!(36.41, result[6].lat);
+        assert_eq!(-76.54, result[6].lon);
+        assert_eq!(36.42, result[7].lat);
+        assert_eq!(-76.66, result[7].lon);
+    }
+
+    #[test]
+    fn get_poly_should_ignore_numbers_outside_the_block() {
+        let text = "* UNTIL 1045 PM CDT.\n\nLAT...LON 3977 9697 3950 9680 3939 9737\nTIME...MOT...LOC 0101Z 233DEG 30KT 3952 9728\n";
+        let regexes = Regexes::new();
+        let result = get_poly(text, &regexes).unwrap();
+        assert_eq!(4, result.len());
+        assert_eq!(39.39, result[2].lat);
+        assert_eq!(39.77, result[3].lat);
+    }
+
+    #[test]
+    fn get_poly_without_block_should_be_empty() {
+        let regexes = Regexes::new();
+        let result = get_poly("1045 PM CDT 3977 9697", &regexes).unwrap();
+        assert!(result.is_empty());
+    }
+
     #[test]
     fn short_time_to_ticks_should_return_correct_ticks() {
         let short_time = "190522T2100Z";
         let result = short_time_to_ticks(short_time).unwrap();
         assert_eq!(result, 1558558800000000);
     }
+
+    #[test]
+    fn get_condensed_coordinates_should_reject_short_points() {
+        assert!(get_condensed_coordinates("35460123").is_ok());
+        let result = get_condensed_coordinates("354601");
+        assert_eq!(Some(FieldError::invalid("point", "354601")), result.err());
+    }
+
+    #[test]
+    fn within_should_offset_into_the_whole_text() {
+        let text = "SEGMENT ONE\n$$\nSEGMENT TWO";
+        let part = &text[15..];
+        let result = FieldError::missing("vtec").at(Some(8)).within(text, part);
+        assert_eq!(FieldError::missing("vtec").at(Some(23)), result);
+    }
 }
@@ -0,0 +1,129 @@
+use super::dead_letter::DeadLetters;
+use super::domain::{NwsEvent, Product};
+use super::hazards::Hazards;
+use super::lsr_parser::ReportLog;
+use super::parser::ParseError;
+use super::registry::Registry;
+use super::sink::Sinks;
+use slog::Logger;
+use std::sync::{Arc, Mutex};
+
+/**
+ * Everything that happens to a product once it's been fetched: parsing, dropping LSR reports
+ * that were already stored, tracking active warnings, and sending the events to the sinks.
+ * Products that can't be parsed are dead-lettered. Polling, backfills, and replays all go
+ * through here so they behave the same.
+ */
+pub struct Pipeline {
+    logger: Logger,
+    registry: Arc<Registry>,
+    hazards: Arc<Mutex<Hazards>>,
+    report_log: Arc<Mutex<ReportLog>>,
+    sinks: Arc<Sinks>,
+    dead_letters: DeadLetters,
+}
+
+impl Pipeline {
+    pub fn new(
+        logger: &Logger,
+        registry: Arc<Registry>,
+        hazards: Arc<Mutex<Hazards>>,
+        report_log: Arc<Mutex<ReportLog>>,
+        sinks: Arc<Sinks>,
+        dead_letters: DeadLetters,
+    ) -> Pipeline {
+        Pipeline {
+            logger: logger.clone(),
+            registry,
+            hazards,
+            report_log,
+            sinks,
+            dead_letters,
+        }
+    }
+
+    /**
+     * Parses and stores a product's events, returning how many reached at least one sink.
+     */
+    pub fn process(&mut self, product: &Product) -> usize {
+        match self.try_process(product) {
+            Ok(value) => value,
+            Err(error) => {
+                error!(self.logger, "Parsing error"; "error" => format!("{}", error));
+                match self.dead_letters.save(product, &error) {
+                    Ok(path) => {
+                        info!(self.logger, "Dead-lettered product"; "path" => path.to_string_lossy().to_string())
+                    }
+                    Err(error) => {
+                        error!(self.logger, "Dead letter error"; "error" => format!("{}", error))
+                    }
+                }
+                0
+            }
+        }
+    }
+
+    /**
+     * Like `process`, but leaves handling parsing errors to the caller.
+     */
+    pub fn try_process(&mut self, product: &Product) -> Result<usize, ParseError> {
+        let events = self.registry.parse(product)?;
+        Ok(self.store(events))
+    }
+
+    fn store(&mut self, events: Vec<NwsEvent>) -> usize {
+        let events = self.log_reports(events);
+        self.hazards.lock().unwrap().apply(&events);
+
+        let mut stored = 0;
+        for event in &events {
+            if self.sinks.put(event, &self.logger) {
+                stored += 1;
+            }
+        }
+
+        stored
+    }
+
+    // Drops reports that were already stored, and saves the ones that are new
+    fn log_reports(&self, events: Vec<NwsEvent>) -> Vec<NwsEvent> {
+        let mut report_log = self.report_log.lock().unwrap();
+        let mut new_events = vec![];
+        for mut event in events {
+            if report_log.insert(&mut event) {
+                new_events.push(event);
+            }
+        }
+
+        if new_events.iter().any(|x| x.lsr.is_some()) {
+            if let Err(error) = report_log.save() {
+                error!(self.logger, "Report log error"; "error" => format!("{}", error));
+            }
+        }
+
+        new_events
+    }
+
+    /**
+     * Retries events that sinks rejected earlier and logs how many are still waiting.
+     */
+    pub fn retry_spooled(&self) {
+        self.sinks.retry_spooled(&self.logger);
+        for (sink, depth) in self.sinks.get_spool_depths() {
+            if depth > 0 {
+                warn!(self.logger, "Spool depth"; "sink" => sink, "depth" => depth);
+            }
+        }
+    }
+
+    /**
+     * Drops hazards that have expired as of `now` (in micros).
+     */
+    pub fn expire_hazards(&self, now: u64) {
+        let mut hazards = self.hazards.lock().unwrap();
+        for expired in hazards.expire(now) {
+            info!(self.logger, "Hazard expired"; "event_id" => expired.event_id);
+        }
+        debug!(self.logger, "Active hazards"; "count" => hazards.get_active(now).len());
+    }
+}
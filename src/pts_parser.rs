@@ -0,0 +1,351 @@
+use super::domain::{NwsEvent, OutlookArea, Product};
+use super::parser::{get_condensed_coordinates, get_issuance_ticks, get_offset, FieldError};
+use wx::domain::{Coordinates, Event, EventType, Outlook, OutlookRisk, SwoType};
+
+// Categorical risks by severity descending
+const RISKS: [&str; 6] = ["HIGH", "MDT", "ENH", "SLGT", "MRGL", "TSTM"];
+const LINE_BREAK: &str = "99999999";
+const SIGNIFICANT: &str = "SIGN";
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum PtsHazard {
+    AnySevere,
+    Categorical,
+    Hail,
+    Tornado,
+    Wind,
+}
+
+// Intermediary structure for a single outlook area, before it becomes an Event
+#[derive(Debug)]
+struct PtsArea<'a> {
+    day: Option<String>,
+    hazard: PtsHazard,
+    label: String,
+    points: Vec<&'a str>,
+}
+
+/**
+ * Parses an SPC Probabilistic Outlook Points (PTS) product, which contains the polygons for the
+ * Day 1/2/3/4-8 outlooks (WUUS01/02/03/48). Each probabilistic, hatched (SIGN), and categorical
+ * area becomes its own outlook event with every polygon drawn for it, ex. both 5% tornado
+ * areas, and its hazard and probability or risk.
+ */
+pub fn parse(product: &Product) -> Result<Vec<NwsEvent>, FieldError> {
+    // Fail fast on unexpected products rather than silently emitting nothing
+    let swo_type = get_swo_type(&product.wmo_collective_id)?;
+    let event_ts = get_issuance_ticks(product)?;
+    let areas = get_areas(&product.product_text);
+    let max_risk = RISKS
+        .iter()
+        .find(|risk| {
+            areas
+                .iter()
+                .any(|x| x.hazard == PtsHazard::Categorical && x.label == **risk)
+        })
+        .unwrap_or(&"TSTM");
+
+    let mut groups: Vec<(&PtsArea, Vec<Vec<Coordinates>>)> = vec![];
+    for area in &areas {
+        let mut poly = vec![];
+        for point in &area.points {
+            let coordinates = get_condensed_coordinates(point)
+                .map_err(|x| x.at(get_offset(&product.product_text, point)))?;
+            poly.push(coordinates);
+        }
+
+        match groups.iter_mut().find(|x| is_same_area(x.0, area)) {
+            Some(group) => group.1.push(poly),
+            None => groups.push((area, vec![poly])),
+        }
+    }
+
+    let mut events = vec![];
+    for (area, polys) in groups {
+        let title = format!("SPC {:?} Outlook: {}", swo_type, get_area_title(area));
+        let outlook = Outlook {
+            swo_type,
+            max_risk: get_outlook_risk(max_risk),
+            polys: Some(polys),
+        };
+
+        let mut event = Event::new(event_ts, EventType::NwsSwo, title);
+        event.outlook = Some(outlook);
+        events.push(NwsEvent {
+            outlook_area: Some(get_outlook_area(area)),
+            ..NwsEvent::from(event)
+        });
+    }
+
+    Ok(events)
+}
+
+// Areas are split up when a label has more than one polygon
+fn is_same_area(a: &PtsArea, b: &PtsArea) -> bool {
+    a.day == b.day && a.hazard == b.hazard && a.label == b.label
+}
+
+fn get_outlook_area(area: &PtsArea) -> OutlookArea {
+    let is_categorical = area.hazard == PtsHazard::Categorical;
+
+    OutlookArea {
+        hazard: area.hazard,
+        probability: if is_categorical {
+            None
+        } else {
+            area.label.parse().ok()
+        },
+        is_significant: area.label == SIGNIFICANT,
+        risk: if is_categorical {
+            Some(get_outlook_risk(&area.label))
+        } else {
+            None
+        },
+        day: area.day.as_ref().and_then(|x| x.parse().ok()),
+    }
+}
+
+fn get_swo_type(wmo_collective_id: &str) -> Result<SwoType, FieldError> {
+    match wmo_collective_id {
+        "WUUS01" => Ok(SwoType::Day1),
+        "WUUS02" => Ok(SwoType::Day2),
+        "WUUS03" => Ok(SwoType::Day3),
+        "WUUS48" => Ok(SwoType::Day48),
+        _ => Err(FieldError::invalid("WMO collective ID", wmo_collective_id)),
+    }
+}
+
+fn get_outlook_risk(label: &str) -> OutlookRisk {
+    match label {
+        "HIGH" => OutlookRisk::HIGH,
+        "MDT" => OutlookRisk::MDT,
+        "ENH" => OutlookRisk::ENH,
+        "SLGT" => OutlookRisk::SLGT,
+        "MRGL" => OutlookRisk::MRGL,
+        _ => OutlookRisk::TSTM,
+    }
+}
+
+fn get_hazard(line: &str) -> Option<PtsHazard> {
+    match line.trim().trim_matches('.').trim() {
+        "ANY SEVERE" => Some(PtsHazard::AnySevere),
+        "CATEGORICAL" => Some(PtsHazard::Categorical),
+        "HAIL" => Some(PtsHazard::Hail),
+        "TORNADO" => Some(PtsHazard::Tornado),
+        "WIND" => Some(PtsHazard::Wind),
+        _ => None,
+    }
+}
+
+fn get_area_title(area: &PtsArea) -> String {
+    let hazard = match area.hazard {
+        PtsHazard::AnySevere => "Severe",
+        PtsHazard::Categorical => "",
+        PtsHazard::Hail => "Hail",
+        PtsHazard::Tornado => "Tornado",
+        PtsHazard::Wind => "Wind",
+    };
+
+    let title = if area.hazard == PtsHazard::Categorical {
+        area.label.to_string()
+    } else if area.label == SIGNIFICANT {
+        format!("Sig {}", hazard)
+    } else {
+        match area.label.parse::<f32>() {
+            Ok(probability) => format!("{}% {}", (probability * 100.0).round(), hazard),
+            Err(_) => format!("{} {}", area.label, hazard),
+        }
+    };
+
+    match &area.day {
+        Some(day) => format!("{} (Day {})", title, day),
+        None => title,
+    }
+}
+
+/**
+ * Walks the product line by line, collecting every outlook area under its hazard heading. Areas
+ * that span multiple lines continue on indented lines, and a `99999999` point starts a new
+ * area with the same label. Some products repeat their entire body, so parsing stops once a
+ * second `VALID TIME` line is found.
+ */
+fn get_areas(text: &str) -> Vec<PtsArea> {
+    let mut areas: Vec<PtsArea> = vec![];
+    let mut hazard = None;
+    let mut day = None;
+    let mut seen_valid_time = false;
+
+    for line in text.lines() {
+        if line.starts_with("VALID TIME") {
+            if seen_valid_time {
+                break;
+            }
+            seen_valid_time = true;
+            continue;
+        }
+
+        // Day 4-8 outlooks have a section per day
+        if line.starts_with("SEVERE WEATHER OUTLOOK POINTS DAY") {
+            day = line.split_whitespace().last().map(ToString::to_string);
+            continue;
+        }
+
+        if line.starts_with("...") {
+            hazard = get_hazard(line);
+            continue;
+        }
+
+        if line.starts_with("&&") {
+            hazard = None;
+            continue;
+        }
+
+        let current_hazard = match hazard {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let mut tokens = line.split_whitespace();
+        let is_continuation = line.starts_with(' ');
+
+        if !is_continuation {
+            let label = match tokens.next() {
+                Some(value) => value.to_string(),
+                None => continue,
+            };
+            areas.push(PtsArea {
+                day: day.clone(),
+                hazard: current_hazard,
+                label,
+                points: vec![],
+            });
+        }
+
+        for token in tokens {
+            let area = match areas.last_mut() {
+                Some(value) => value,
+                None => break,
+            };
+
+            if token == LINE_BREAK {
+                let next_area = PtsArea {
+                    day: area.day.clone(),
+                    hazard: area.hazard,
+                    label: area.label.to_string(),
+                    points: vec![],
+                };
+                areas.push(next_area);
+            } else if token.len() == 8 && token.chars().all(|x| x.is_ascii_digit()) {
+                area.points.push(token);
+            }
+        }
+    }
+
+    areas.retain(|x| !x.points.is_empty());
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::get_product_from_file;
+    use super::*;
+
+    #[test]
+    fn parse_pts_day1_should_emit_every_area() {
+        let product = get_product_from_file("data/products/pts-two-sections.dy1");
+        let result = parse(&product).unwrap();
+        let titles: Vec<&str> = result.iter().map(|x| x.event.title.as_str()).collect();
+        let expected = vec![
+            "SPC Day1 Outlook: 2% Tornado",
+            "SPC Day1 Outlook: 5% Tornado",
+            "SPC Day1 Outlook: 10% Tornado",
+            "SPC Day1 Outlook: Sig Tornado",
+            "SPC Day1 Outlook: 5% Hail",
+            "SPC Day1 Outlook: 15% Hail",
+            "SPC Day1 Outlook: 30% Hail",
+            "SPC Day1 Outlook: Sig Hail",
+            "SPC Day1 Outlook: 5% Wind",
+            "SPC Day1 Outlook: 15% Wind",
+            "SPC Day1 Outlook: 30% Wind",
+            "SPC Day1 Outlook: ENH",
+            "SPC Day1 Outlook: SLGT",
+            "SPC Day1 Outlook: MRGL",
+            "SPC Day1 Outlook: TSTM",
+        ];
+        assert_eq!(expected, titles);
+    }
+
+    #[test]
+    fn parse_pts_should_convert_points() {
+        let product = get_product_from_file("data/products/pts-two-sections.dy1");
+        let result = parse(&product).unwrap();
+        let outlook = result[2].event.outlook.as_ref().unwrap();
+        let serialized_result = serde_json::to_string(&outlook.polys).unwrap();
+        let expected = r#"[[{"lat":39.76,"lon":-97.29},{"lat":40.03,"lon":-97.01},{"lat":40.27,"lon":-96.65},{"lat":40.25,"lon":-96.52},{"lat":40.24,"lon":-96.36},{"lat":39.6,"lon":-96.24},{"lat":39.26,"lon":-96.48},{"lat":38.86,"lon":-96.72},{"lat":38.67,"lon":-97.24},{"lat":38.58,"lon":-97.42},{"lat":38.51,"lon":-97.68},{"lat":38.41,"lon":-97.93},{"lat":38.34,"lon":-98.24},{"lat":38.31,"lon":-98.79},{"lat":38.42,"lon":-98.93},{"lat":38.69,"lon":-98.95},{"lat":38.88,"lon":-98.64},{"lat":39.4,"lon":-97.79},{"lat":39.76,"lon":-97.29}]]"#;
+        assert_eq!(expected, serialized_result);
+    }
+
+    #[test]
+    fn parse_pts_should_key_areas_by_hazard_and_probability() {
+        let product = get_product_from_file("data/products/pts-two-sections.dy1");
+        let result = parse(&product).unwrap();
+        let area = result[2].outlook_area.as_ref().unwrap();
+        assert_eq!(PtsHazard::Tornado, area.hazard);
+        assert_eq!(Some(0.1), area.probability);
+        assert!(!area.is_significant);
+        assert!(area.risk.is_none());
+
+        let area = result[3].outlook_area.as_ref().unwrap();
+        assert_eq!(None, area.probability);
+        assert!(area.is_significant);
+
+        let area = result[11].outlook_area.as_ref().unwrap();
+        assert_eq!(PtsHazard::Categorical, area.hazard);
+        assert_eq!(
+            "\"ENH\"",
+            serde_json::to_string(&area.risk.as_ref().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_pts_should_group_polygons_split_on_line_breaks() {
+        let product = get_product_from_file("data/products/pts-dy3");
+        let result = parse(&product).unwrap();
+        let titles: Vec<&str> = result.iter().map(|x| x.event.title.as_str()).collect();
+        let expected = vec![
+            "SPC Day3 Outlook: 5% Severe",
+            "SPC Day3 Outlook: 15% Severe",
+            "SPC Day3 Outlook: SLGT",
+            "SPC Day3 Outlook: MRGL",
+            "SPC Day3 Outlook: TSTM",
+        ];
+        assert_eq!(expected, titles);
+        let polys = result[0].event.outlook.as_ref().unwrap().polys.as_ref();
+        assert_eq!(Some(2), polys.map(|x| x.len()));
+    }
+
+    #[test]
+    fn parse_pts_day48_should_label_days() {
+        let product = get_product_from_file("data/products/pts-d48");
+        let result = parse(&product).unwrap();
+        let titles: Vec<&str> = result.iter().map(|x| x.event.title.as_str()).collect();
+        let expected = vec![
+            "SPC Day48 Outlook: 15% Severe (Day 4)",
+            "SPC Day48 Outlook: 30% Severe (Day 4)",
+        ];
+        assert_eq!(expected, titles);
+        let area = result[0].outlook_area.as_ref().unwrap();
+        assert_eq!(Some(4), area.day);
+    }
+
+    #[test]
+    fn get_area_title_should_format_probabilities() {
+        let area = PtsArea {
+            day: None,
+            hazard: PtsHazard::Tornado,
+            label: "0.45".to_string(),
+            points: vec![],
+        };
+        assert_eq!("45% Tornado", get_area_title(&area));
+    }
+}
@@ -0,0 +1,264 @@
+use super::afd_parser;
+use super::domain::{NwsEvent, Product};
+use super::ffw_parser;
+use super::lsr_parser;
+use super::parser::{FieldError, ParseError, Regexes};
+use super::pts_parser;
+use super::sel_parser;
+use super::sev_parser;
+use super::svr_parser;
+use super::svs_parser;
+use super::swo_parser;
+use super::tor_parser;
+use super::wou_parser;
+
+/**
+ * Turns the products for a product code into events. Registering a parser is all it takes for
+ * its code to be accepted by the config and backfills, polled, and dispatched to.
+ */
+pub trait ProductParser: Send + Sync {
+    fn code(&self) -> &str;
+    fn parse(&self, product: &Product, regexes: Regexes) -> Result<Vec<NwsEvent>, FieldError>;
+
+    // How often to poll for the product when the config doesn't say, or None for the 60s default
+    fn poll_interval_ms(&self) -> Option<u64> {
+        None
+    }
+}
+
+type ParseFn = fn(&Product, Regexes) -> Result<Vec<NwsEvent>, FieldError>;
+
+// One of the parser modules in this crate
+struct Builtin {
+    code: &'static str,
+    parse: ParseFn,
+    poll_interval_ms: Option<u64>,
+}
+
+impl ProductParser for Builtin {
+    fn code(&self) -> &str {
+        self.code
+    }
+
+    fn parse(&self, product: &Product, regexes: Regexes) -> Result<Vec<NwsEvent>, FieldError> {
+        (self.parse)(product, regexes)
+    }
+
+    fn poll_interval_ms(&self) -> Option<u64> {
+        self.poll_interval_ms
+    }
+}
+
+/**
+ * The parser for each supported product code. Both the poller and the dispatcher go through
+ * here, so adding a product only means registering its parser.
+ */
+pub struct Registry {
+    parsers: Vec<Box<dyn ProductParser>>,
+}
+
+impl Default for Registry {
+    fn default() -> Registry {
+        Registry::new()
+    }
+}
+
+impl Registry {
+    /**
+     * Returns a registry with every built-in parser. Warnings and reports are the most time
+     * sensitive, so they're polled more often than the 60s default when the config doesn't set
+     * an interval for them, either per product or at the top level.
+     */
+    pub fn new() -> Registry {
+        // code, parse, poll_interval_ms
+        let builtins: [(&'static str, ParseFn, Option<u64>); 12] = [
+            ("AFD", |x, _| into_events(afd_parser::parse(x)), None),
+            (
+                "FFW",
+                |x, r| into_events(ffw_parser::parse(x, r)),
+                Some(30_000),
+            ),
+            (
+                "LSR",
+                |x, _| into_events(lsr_parser::parse(x)),
+                Some(30_000),
+            ),
+            ("PTS", |x, _| into_events(pts_parser::parse(x)), None),
+            ("SEL", |x, r| into_events(sel_parser::parse(x, r)), None),
+            ("SEV", |x, r| into_events(sev_parser::parse(x, r)), None),
+            (
+                "SVR",
+                |x, r| into_events(svr_parser::parse(x, r)),
+                Some(15_000),
+            ),
+            (
+                "SVS",
+                |x, r| into_events(svs_parser::parse(x, r)),
+                Some(15_000),
+            ),
+            ("SWO", |x, r| into_events(swo_parser::parse(x, r)), None),
+            (
+                "TOR",
+                |x, r| into_events(tor_parser::parse(x, r)),
+                Some(15_000),
+            ),
+            ("WCN", |x, r| into_events(wou_parser::parse(x, r)), None),
+            ("WOU", |x, r| into_events(wou_parser::parse(x, r)), None),
+        ];
+
+        let mut registry = Registry { parsers: vec![] };
+        for &(code, parse, poll_interval_ms) in builtins.iter() {
+            registry.register(Box::new(Builtin {
+                code,
+                parse,
+                poll_interval_ms,
+            }));
+        }
+
+        registry
+    }
+
+    /**
+     * Adds a parser, replacing any that was already registered for its product code.
+     */
+    pub fn register(&mut self, parser: Box<dyn ProductParser>) {
+        self.parsers
+            .retain(|x| !x.code().eq_ignore_ascii_case(parser.code()));
+        self.parsers.push(parser);
+    }
+
+    // The API uses lowercase product codes and the products themselves use uppercase
+    pub fn get(&self, code: &str) -> Option<&dyn ProductParser> {
+        self.parsers
+            .iter()
+            .find(|x| x.code().eq_ignore_ascii_case(code))
+            .map(|x| x.as_ref())
+    }
+
+    pub fn codes(&self) -> Vec<&str> {
+        self.parsers.iter().map(|x| x.code()).collect()
+    }
+
+    /**
+     * Hands a product to the parser for its code. Parsers report what they couldn't read as a
+     * FieldError, which is tagged with the product here.
+     */
+    pub fn parse(&self, product: &Product) -> Result<Vec<NwsEvent>, ParseError> {
+        let parser = match self.get(&product.product_code) {
+            Some(value) => value,
+            None => {
+                return Err(ParseError::UnsupportedProduct {
+                    product_id: product.id.to_string(),
+                    product_code: product.product_code.to_string(),
+                })
+            }
+        };
+
+        parser
+            .parse(product, Regexes::new())
+            .map_err(|x| ParseError::new(product, x))
+    }
+}
+
+/**
+ * Parsers return whatever shape fits their product: most map to at most one event, some
+ * (ex. PTS) contain several, and some carry extensions beyond the shared Event.
+ */
+fn into_events<T, E>(result: Result<T, FieldError>) -> Result<Vec<NwsEvent>, FieldError>
+where
+    T: IntoIterator<Item = E>,
+    E: Into<NwsEvent>,
+{
+    result.map(|x| x.into_iter().map(Into::into).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::get_product_from_file;
+
+    struct FixedParser;
+
+    impl ProductParser for FixedParser {
+        fn code(&self) -> &str {
+            "TOR"
+        }
+
+        fn parse(&self, _: &Product, _: Regexes) -> Result<Vec<NwsEvent>, FieldError> {
+            Err(FieldError::missing("fixed"))
+        }
+    }
+
+    #[test]
+    fn get_should_ignore_case() {
+        let registry = Registry::new();
+        assert_eq!("TOR", registry.get("tor").unwrap().code());
+        assert_eq!(
+            Some(15_000),
+            registry.get("tor").unwrap().poll_interval_ms()
+        );
+        assert_eq!(None, registry.get("afd").unwrap().poll_interval_ms());
+        assert!(registry.get("ffa").is_none());
+    }
+
+    #[test]
+    fn register_should_replace_the_parser_for_a_code() {
+        let mut registry = Registry::new();
+        let count = registry.codes().len();
+        registry.register(Box::new(FixedParser));
+        assert_eq!(count, registry.codes().len());
+
+        let product = get_product_from_file("data/products/tor-normal");
+        let result = registry.parse(&product).unwrap_err();
+        assert_eq!(
+            format!("missing fixed in TOR {}", product.id),
+            result.to_string()
+        );
+    }
+
+    #[test]
+    fn parse_unknown_product_should_be_unsupported() {
+        let mut product = get_product_from_file("data/products/tor-normal");
+        product.product_code = "XYZ".to_string();
+        let result = Registry::new().parse(&product).unwrap_err();
+        assert_eq!(
+            ParseError::UnsupportedProduct {
+                product_id: product.id.to_string(),
+                product_code: "XYZ".to_string(),
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn parse_truncated_product_should_be_missing_a_field() {
+        let mut product = get_product_from_file("data/products/tor-normal");
+        let end = product.product_text.find("LAT...LON").unwrap();
+        product.product_text.truncate(end);
+        let result = Registry::new().parse(&product).unwrap_err();
+        assert_eq!(
+            format!("missing movement in TOR {}", product.id),
+            result.to_string()
+        );
+    }
+
+    #[test]
+    fn parse_bad_vtec_time_should_be_invalid_at_its_offset() {
+        let mut product = get_product_from_file("data/products/tor-normal");
+        product.product_text = product
+            .product_text
+            .replace("190422T2353Z-", "191322T2353Z-");
+        let expected_offset = product.product_text.find("191322T2353Z");
+        let result = Registry::new().parse(&product).unwrap_err();
+        assert_eq!(
+            ParseError::InvalidField {
+                product_id: product.id.to_string(),
+                product_code: "TOR".to_string(),
+                field: "begin",
+                value: "191322T2353Z".to_string(),
+                offset: expected_offset,
+            },
+            result
+        );
+    }
+}
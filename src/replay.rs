@@ -0,0 +1,211 @@
+use super::config::Config;
+use super::dead_letter::DeadLetters;
+use super::domain::Product;
+use super::hazards::Hazards;
+use super::lsr_parser::ReportLog;
+use super::pipeline::Pipeline;
+use super::registry::Registry;
+use super::sink::Sinks;
+use slog::Logger;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use wx::error::{Error, WxError};
+use wx::util;
+
+pub const REPLAY_COMMAND: &str = "replay";
+const USAGE: &str = "replay <directory or .jsonl file> [speed], ex. replay data/products 60";
+
+/**
+ * Pushes saved products through the same pipeline as polling, without the network. Used to
+ * rehearse downstream consumers against a past severe weather day.
+ */
+#[derive(Debug, PartialEq)]
+pub struct Replay {
+    pub path: String,
+    // Multiple of real time to replay at, or as fast as possible if not set
+    pub speed: Option<f64>,
+}
+
+impl Replay {
+    pub fn from_args(args: &[String]) -> Result<Replay, Error> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(get_replay_error(USAGE));
+        }
+
+        let speed = match args.get(1) {
+            Some(value) => match value.parse::<f64>() {
+                Ok(speed) if speed > 0.0 => Some(speed),
+                _ => return Err(get_replay_error(&format!("invalid speed: {}", value))),
+            },
+            None => None,
+        };
+
+        Ok(Replay {
+            path: args[0].to_string(),
+            speed,
+        })
+    }
+
+    /**
+     * Replays every product in issuance order, waiting between them as long as the gap in
+     * issuance times (scaled by the speed) if one was given. Hazards expire as of each
+     * product's issuance time rather than the clock. LSR reports are only deduplicated within
+     * the replay, not against `report_log_path`. Returns the number of events stored.
+     */
+    pub fn run(
+        &self,
+        config: &Config,
+        registry: Arc<Registry>,
+        logger: &Logger,
+    ) -> Result<usize, Error> {
+        let products = load_products(&self.path, logger)?;
+        info!(logger, "replaying"; "path" => &self.path, "products" => products.len());
+        let sinks = Arc::new(Sinks::from_config(&config.sinks, &config.spool)?);
+        let dead_letters = DeadLetters::new(&config.dead_letter_dir);
+        let hazards = Arc::new(Mutex::new(Hazards::new()));
+        // Fresh, so a day that was stored live still replays in full
+        let report_log = Arc::new(Mutex::new(ReportLog::new()));
+        let mut pipeline =
+            Pipeline::new(logger, registry, hazards, report_log, sinks, dead_letters);
+        let mut previous_ts = None;
+        let mut stored = 0;
+
+        for (ts, product) in products {
+            if let (Some(previous_ts), Some(speed)) = (previous_ts, self.speed) {
+                thread::sleep(get_delay(previous_ts, ts, speed));
+            }
+
+            pipeline.expire_hazards(ts);
+            stored += pipeline.process(&product);
+            previous_ts = Some(ts);
+        }
+
+        Ok(stored)
+    }
+}
+
+/**
+ * Reads products from either a directory with one product JSON file each (like the API
+ * returns), or a JSONL archive with one product per line. Anything that isn't a product is
+ * logged and skipped. Products are returned oldest first, with their issuance time.
+ */
+pub fn load_products(path: &str, logger: &Logger) -> Result<Vec<(u64, Product)>, Error> {
+    let mut contents: Vec<(String, String)> = vec![];
+
+    if Path::new(path).is_dir() {
+        let mut paths: Vec<_> = fs::read_dir(path)?
+            .filter_map(Result::ok)
+            .map(|x| x.path())
+            .filter(|x| x.is_file())
+            .collect();
+        paths.sort();
+
+        for file_path in paths {
+            let source = file_path.to_string_lossy().to_string();
+            contents.push((source, fs::read_to_string(&file_path)?));
+        }
+    } else {
+        let archive = fs::read_to_string(path)?;
+        contents.extend(
+            archive
+                .lines()
+                .enumerate()
+                .filter(|x| !x.1.trim().is_empty())
+                .map(|(i, line)| (format!("{}:{}", path, i + 1), line.to_string())),
+        );
+    }
+
+    let mut products = vec![];
+
+    for (source, content) in contents {
+        let product = match serde_json::from_str::<Product>(&content) {
+            Ok(value) => value,
+            Err(error) => {
+                warn!(logger, "skipping invalid product"; "source" => source, "error" => format!("{}", error));
+                continue;
+            }
+        };
+
+        match util::ts_to_ticks(&product.issuance_time) {
+            Ok(ts) => products.push((ts, product)),
+            Err(_) => {
+                warn!(logger, "skipping product with invalid issuance time"; "source" => source)
+            }
+        }
+    }
+
+    // Stable, so products issued the same minute keep their original order
+    products.sort_by_key(|x| x.0);
+    Ok(products)
+}
+
+fn get_delay(previous_ts: u64, ts: u64, speed: f64) -> Duration {
+    let gap_micros = ts.saturating_sub(previous_ts) as f64 / speed;
+    Duration::from_micros(gap_micros as u64)
+}
+
+fn get_replay_error(reason: &str) -> Error {
+    let reason = format!("invalid replay: {}", reason);
+    Error::Wx(<WxError>::new(&reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::{get_logger, get_product_from_file};
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn from_args_should_read_the_speed() {
+        let args = vec!["data/products".to_string(), "60".to_string()];
+        let expected = Replay {
+            path: "data/products".to_string(),
+            speed: Some(60.0),
+        };
+        assert_eq!(expected, Replay::from_args(&args).unwrap());
+    }
+
+    #[test]
+    fn from_args_zero_speed_should_be_an_error() {
+        let args = vec!["data/products".to_string(), "0".to_string()];
+        assert!(Replay::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn get_delay_should_scale_the_gap() {
+        let result = get_delay(1555977060000000, 1555977120000000, 60.0);
+        assert_eq!(Duration::from_secs(1), result);
+        assert_eq!(Duration::from_secs(0), get_delay(10, 5, 1.0));
+    }
+
+    #[test]
+    fn load_products_from_directory_should_be_in_issuance_order() {
+        let logger = get_logger();
+        let products = load_products("data/products", &logger).unwrap();
+        assert!(products.len() > 40);
+        assert!(products.windows(2).all(|x| x[0].0 <= x[1].0));
+    }
+
+    #[test]
+    fn load_products_from_jsonl_should_be_in_issuance_order() {
+        let path = env::temp_dir().join("nws-api-loader-replay.jsonl");
+        let tor = get_product_from_file("data/products/tor-normal");
+        let svr = get_product_from_file("data/products/svr");
+        let lines = vec![
+            serde_json::to_string(&tor).unwrap(),
+            "not a product".to_string(),
+            String::new(),
+            serde_json::to_string(&svr).unwrap(),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let logger = get_logger();
+        let products = load_products(path.to_str().unwrap(), &logger).unwrap();
+        let _ = fs::remove_file(&path);
+        let ids: Vec<&str> = products.iter().map(|x| x.1.id.as_str()).collect();
+        assert_eq!(vec![svr.id.as_str(), tor.id.as_str()], ids);
+    }
+}
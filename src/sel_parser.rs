@@ -1,25 +1,22 @@
 use super::domain::Product;
-use super::parser::{get_parse_error, Regexes};
+use super::parser::{get_issuance_ticks, parse_cap, FieldError, Regexes};
 use wx::domain::{Event, EventType, Watch, WatchStatus, WatchType};
-use wx::error::Error;
-use wx::util;
 
-pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, FieldError> {
     let text = &product.product_text;
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
+    let event_ts = get_issuance_ticks(product)?;
     let lower_case_text = text.to_lowercase();
     let is_pds = lower_case_text.contains("particularly dangerous situation");
     let id = regexes
         .watch_id
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let id = id[1].parse::<u16>()?;
+        .ok_or_else(|| FieldError::missing("watch id"))?;
+    let id = parse_cap::<u16>(&id, "id")?;
     let mut issued_for = None;
     let raw_issued_for = regexes.watch_for.captures(&text);
 
-    if raw_issued_for.is_some() {
-        let raw_issued_for = raw_issued_for.unwrap();
-        let raw_issued_for = raw_issued_for[1].trim();
+    if let Some(raw_issued_for) = raw_issued_for.as_ref().and_then(|x| x.get(1)) {
+        let raw_issued_for = raw_issued_for.as_str().trim();
         let raw_issued_for = raw_issued_for.replace("\n  ", ", ");
         issued_for = Some(raw_issued_for);
     }
@@ -0,0 +1,129 @@
+use super::domain::Product;
+use super::parser::{
+    cap, close_ring, get_invalid, get_issuance_ticks, get_offset, parse_cap, short_time_to_ticks,
+    FieldError, Regexes,
+};
+use regex::Captures;
+use wx::domain::{Coordinates, Event, EventType, Location, Watch, WatchStatus, WatchType};
+
+const DAY_MICROS: u64 = 24 * 60 * 60 * 1000 * 1000;
+
+/**
+ * Parses an SPC Watch Point Information Message (SEV), which lists the vertices of every
+ * currently active watch. Each watch becomes its own event, and an empty list means no
+ * watches are active.
+ */
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Vec<Event>, FieldError> {
+    let text = &product.product_text;
+    let event_ts = get_issuance_ticks(product)?;
+    let mut events = vec![];
+
+    for captures in regexes.sev_watch.captures_iter(&text) {
+        let date = cap(&captures, "date")?;
+        let id = parse_cap::<u16>(&captures, "id")?;
+        let valid_ts = get_ticks(&captures, date, "start")?;
+        let mut expires_ts = get_ticks(&captures, date, "end")?;
+
+        // Only the start date is given, so watches that cross 00Z expire the next day
+        if expires_ts <= valid_ts {
+            expires_ts += DAY_MICROS;
+        }
+
+        let (watch_type, watch_type_text) = match cap(&captures, "type")? {
+            "WT" => (WatchType::Tornado, "Tor "),
+            "WS" => (WatchType::SevereThunderstorm, "Tstm "),
+            _ => (WatchType::Other, ""),
+        };
+
+        let mut poly = vec![];
+        for vertex in cap(&captures, "points")?.split_whitespace() {
+            poly.push(get_coordinates(vertex).map_err(|x| x.at(get_offset(&text, vertex)))?);
+        }
+        // Vertices are listed without repeating the first, like warnings
+        close_ring(&mut poly);
+
+        let title = format!("SPC active {}Watch {}", watch_type_text, id);
+
+        let watch = Watch {
+            is_pds: false,
+            id,
+            issued_for: None,
+            watch_type,
+            status: WatchStatus::Issued,
+        };
+
+        let location = Location {
+            wfo: None,
+            point: None,
+            poly: Some(poly),
+            county: None,
+        };
+
+        let mut event = Event::new(event_ts, EventType::NwsSel, title);
+        event.expires_ts = Some(expires_ts);
+        event.location = Some(location);
+        event.valid_ts = Some(valid_ts);
+        event.watch = Some(watch);
+        events.push(event);
+    }
+
+    Ok(events)
+}
+
+// Watch times only have hours and minutes, ex. `2345`, so they need the date they start on
+fn get_ticks(captures: &Captures, date: &str, name: &'static str) -> Result<u64, FieldError> {
+    let time = cap(captures, name)?;
+    short_time_to_ticks(&format!("{}T{}Z", date, time)).map_err(|_| get_invalid(captures, name))
+}
+
+/**
+ * SEV vertices are `ddddd.ddddd` with implied decimals, ex. `03214.09816` is 32.14, -98.16.
+ */
+fn get_coordinates(input: &str) -> Result<Coordinates, FieldError> {
+    let invalid = || FieldError::invalid("point", input);
+
+    match input.split('.').collect::<Vec<&str>>().as_slice() {
+        [lat, lon] => Ok(Coordinates {
+            lat: lat.parse::<f32>().map_err(|_| invalid())? / 100.0,
+            lon: lon.parse::<f32>().map_err(|_| invalid())? / -100.0,
+        }),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::get_product_from_file;
+    use super::*;
+
+    #[test]
+    fn parse_sev_no_watches_should_be_empty() {
+        let product = get_product_from_file("data/products/sev-no-watches");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn parse_sev_tor_and_svr_watches() {
+        let product = get_product_from_file("data/products/sev-tor-and-svr-watches");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        let serialized_result = serde_json::to_string(&result).unwrap();
+        let expected = r#"[{"event_ts":1522775700000000,"event_type":"NwsSel","expires_ts":1522796400000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":null,"point":null,"poly":[{"lat":32.14,"lon":-98.16},{"lat":33.56,"lon":-93.2},{"lat":31.46,"lon":-93.2},{"lat":30.03,"lon":-98.16},{"lat":32.14,"lon":-98.16}],"county":null},"md":null,"outlook":null,"report":null,"text":null,"title":"SPC active Tstm Watch 25","valid_ts":1522769100000000,"warning":null,"watch":{"is_pds":false,"id":25,"watch_type":"SevereThunderstorm","status":"Issued","issued_for":null}},{"event_ts":1522775700000000,"event_type":"NwsSel","expires_ts":1522800000000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":null,"point":null,"poly":[{"lat":39.55,"lon":-87.36},{"lat":40.44,"lon":-82.44},{"lat":38.43,"lon":-82.44},{"lat":37.54,"lon":-87.36},{"lat":39.55,"lon":-87.36}],"county":null},"md":null,"outlook":null,"report":null,"text":null,"title":"SPC active Tor Watch 26","valid_ts":1522775700000000,"warning":null,"watch":{"is_pds":false,"id":26,"watch_type":"Tornado","status":"Issued","issued_for":null}}]"#;
+        assert_eq!(expected, serialized_result);
+    }
+
+    #[test]
+    fn get_coordinates_should_parse_implied_decimals() {
+        let result = get_coordinates("03955.08736").unwrap();
+        assert_eq!(39.55, result.lat);
+        assert_eq!(-87.36, result.lon);
+    }
+
+    #[test]
+    fn get_coordinates_should_reject_bad_vertices() {
+        let result = get_coordinates("0395508736");
+        assert!(result.is_err());
+    }
+}
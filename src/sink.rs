@@ -0,0 +1,565 @@
+use super::config::{SinkConfig, SpoolConfig};
+use super::domain::NwsEvent;
+use super::spool::Spool;
+use slog::Logger;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use wx::domain::Event;
+use wx::error::{Error, WxError};
+
+/**
+ * Somewhere events are sent once they're parsed.
+ */
+pub trait Sink: Send {
+    fn name(&self) -> &str;
+    fn put(&mut self, event: &NwsEvent) -> Result<(), Error>;
+}
+
+struct Entry {
+    sink: Box<dyn Sink>,
+    spool: Option<Spool>,
+}
+
+/**
+ * Fans each event out to every configured sink, one after another on the caller's thread, so a
+ * slow sink (ex. a webhook waiting out its timeout) delays the rest and the worker that sent
+ * the event. Each sink has its own lock, so workers only wait on each other for the same sink.
+ * A failure in one doesn't stop the event reaching the rest, and events a sink rejects go to
+ * its spool, if it has one, to be retried later.
+ */
+pub struct Sinks {
+    entries: Vec<Mutex<Entry>>,
+}
+
+impl Sinks {
+    pub fn new(sinks: Vec<Box<dyn Sink>>) -> Sinks {
+        Sinks {
+            entries: sinks
+                .into_iter()
+                .map(|sink| Mutex::new(Entry { sink, spool: None }))
+                .collect(),
+        }
+    }
+
+    /**
+     * Builds the configured sinks, each spooled to a file under the spool dir named for its
+     * type and target (ex. `webhook-http___localhost_8080_events.jsonl`), so reordering sinks
+     * doesn't send one's spooled events to another.
+     */
+    pub fn from_config(configs: &[SinkConfig], spool_config: &SpoolConfig) -> Result<Sinks, Error> {
+        fs::create_dir_all(&spool_config.dir)?;
+        let mut entries = vec![];
+
+        for config in configs {
+            let sink: Box<dyn Sink> = match config {
+                SinkConfig::Store => Box::new(StoreSink::new()),
+                SinkConfig::Stdout => Box::new(StdoutSink),
+                SinkConfig::File {
+                    path,
+                    max_bytes,
+                    max_files,
+                } => Box::new(FileSink::open(path, *max_bytes, *max_files)?),
+                SinkConfig::Webhook { url, timeout_ms } => {
+                    Box::new(WebhookSink::new(url, *timeout_ms)?)
+                }
+            };
+
+            let path = Path::new(&spool_config.dir).join(config.get_spool_name());
+            let spool = Spool::open(
+                path,
+                Duration::from_millis(spool_config.retry_base_ms),
+                Duration::from_millis(spool_config.retry_max_ms),
+            )?;
+            entries.push(Mutex::new(Entry {
+                sink,
+                spool: Some(spool),
+            }));
+        }
+
+        Ok(Sinks { entries })
+    }
+
+    /**
+     * Sends an event to every sink, returning whether at least one accepted or spooled it.
+     */
+    pub fn put(&self, event: &NwsEvent, logger: &Logger) -> bool {
+        let mut is_stored = false;
+
+        for entry in &self.entries {
+            let mut entry = entry.lock().unwrap();
+            let Entry { sink, spool } = &mut *entry;
+
+            // Anything already spooled goes first, so events stay in order. Only a batch is
+            // sent here, and the rest drains on later events or retries.
+            let is_spooling = match spool.as_mut() {
+                Some(spool) => {
+                    spool.retry(sink.as_mut(), logger);
+                    spool.depth() > 0
+                }
+                None => false,
+            };
+
+            if !is_spooling {
+                match sink.put(event) {
+                    Ok(_) => {
+                        debug!(logger, "Stored event"; "sink" => sink.name());
+                        is_stored = true;
+                        continue;
+                    }
+                    Err(error) => {
+                        error!(logger, "Sink error"; "sink" => sink.name(), "error" => format!("{}", error))
+                    }
+                }
+            }
+
+            if let Some(spool) = spool.as_mut() {
+                match spool.push(event) {
+                    Ok(_) => {
+                        info!(logger, "Spooled event"; "sink" => sink.name(), "depth" => spool.depth());
+                        is_stored = true;
+                    }
+                    Err(error) => {
+                        error!(logger, "Spool error"; "sink" => sink.name(), "error" => format!("{}", error))
+                    }
+                }
+            }
+        }
+
+        is_stored
+    }
+
+    /**
+     * Retries spooled events for any sink whose backoff has passed, for when no new events
+     * are coming in to trigger it.
+     */
+    pub fn retry_spooled(&self, logger: &Logger) {
+        for entry in &self.entries {
+            let mut entry = entry.lock().unwrap();
+            let Entry { sink, spool } = &mut *entry;
+            if let Some(spool) = spool.as_mut() {
+                spool.retry(sink.as_mut(), logger);
+            }
+        }
+    }
+
+    /**
+     * Returns how many events are spooled for each sink.
+     */
+    pub fn get_spool_depths(&self) -> Vec<(String, usize)> {
+        self.entries
+            .iter()
+            .map(|x| {
+                let entry = x.lock().unwrap();
+                let depth = entry.spool.as_ref().map(Spool::depth).unwrap_or(0);
+                (entry.sink.name().to_string(), depth)
+            })
+            .collect()
+    }
+}
+
+/**
+ * The shared wx event store. It only takes the base Event, which has nowhere for NWS-specific
+ * details, so an event that has any is stored with the whole NwsEvent as JSON in its text. The
+ * JSON keeps the original text under `text`, so nothing is lost.
+ */
+pub struct StoreSink {
+    client: wx::store::Client,
+}
+
+impl StoreSink {
+    pub fn new() -> StoreSink {
+        StoreSink {
+            client: wx::store::Client::new(),
+        }
+    }
+}
+
+impl Default for StoreSink {
+    fn default() -> StoreSink {
+        StoreSink::new()
+    }
+}
+
+impl Sink for StoreSink {
+    fn name(&self) -> &str {
+        "store"
+    }
+
+    fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+        self.client
+            .put_event(&get_store_event(event)?)
+            .map(|_| ())
+            .map_err(|_| get_sink_error("unable to store event"))
+    }
+}
+
+/**
+ * Writes each event as a line of JSON to stdout.
+ */
+pub struct StdoutSink;
+
+impl Sink for StdoutSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+        let line = serde_json::to_string(event)?;
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        writeln!(handle, "{}", line)?;
+        Ok(())
+    }
+}
+
+/**
+ * Appends each event as a line of JSON to a file. Once the file would grow past `max_bytes`
+ * it's rotated to `<path>.1`, shifting older files up, and only `max_files` of those are kept.
+ */
+pub struct FileSink {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl FileSink {
+    pub fn open(path: &str, max_bytes: u64, max_files: usize) -> Result<FileSink, Error> {
+        let path = PathBuf::from(path);
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(FileSink {
+            path,
+            max_bytes,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn get_rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        let oldest = self.get_rotated_path(self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+
+        for index in (1..self.max_files).rev() {
+            let from = self.get_rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.get_rotated_path(index + 1))?;
+            }
+        }
+
+        if self.max_files > 0 {
+            fs::rename(&self.path, self.get_rotated_path(1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Sink for FileSink {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+        let line = format!("{}\n", serde_json::to_string(event)?);
+        let len = line.len() as u64;
+
+        if self.size > 0 && self.size + len > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += len;
+        Ok(())
+    }
+}
+
+/**
+ * POSTs each event as JSON to a URL, treating anything other than a 2xx as a failure.
+ */
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str, timeout_ms: u64) -> Result<WebhookSink, Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(timeout_ms))
+            .build()
+            .map_err(|e| get_sink_error(&format!("unable to build webhook client: {}", e)))?;
+
+        Ok(WebhookSink {
+            client,
+            url: url.to_string(),
+        })
+    }
+}
+
+impl Sink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .map_err(|e| get_sink_error(&format!("unable to post event: {}", e)))?;
+
+        if !response.status().is_success() {
+            let msg = format!("unexpected status code: {}", response.status());
+            return Err(get_sink_error(&msg));
+        }
+
+        Ok(())
+    }
+}
+
+fn open_append(path: &PathBuf) -> Result<File, Error> {
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+// Event isn't Clone, so the copy sent to the store is made through its JSON
+fn get_store_event(event: &NwsEvent) -> Result<Event, Error> {
+    let mut value = serde_json::to_value(&event.event)?;
+    if event.has_details() {
+        value["text"] = serde_json::Value::String(serde_json::to_string(event)?);
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+fn get_sink_error(reason: &str) -> Error {
+    Error::Wx(<WxError>::new(reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::domain::{LsrDetails, OutlookArea, WatchCounties};
+    use super::super::lsr_parser::LsrEventType;
+    use super::super::parser::Regexes;
+    use super::super::pts_parser::PtsHazard;
+    use super::super::test_util::{get_event, get_logger, get_product_from_file};
+    use super::super::tor_parser;
+    use super::*;
+    use std::env;
+    use std::sync::Arc;
+
+    struct TestSink {
+        is_failing: bool,
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Sink for TestSink {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+            if self.is_failing {
+                return Err(get_sink_error("failing"));
+            }
+            self.events
+                .lock()
+                .unwrap()
+                .push(event.event.title.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn put_should_reach_sinks_after_a_failure() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sinks = Sinks::new(vec![
+            Box::new(TestSink {
+                is_failing: true,
+                events: events.clone(),
+            }),
+            Box::new(TestSink {
+                is_failing: false,
+                events: events.clone(),
+            }),
+        ]);
+        assert!(sinks.put(&get_event("a"), &get_logger()));
+        assert_eq!(vec!["a"], *events.lock().unwrap());
+    }
+
+    #[test]
+    fn put_without_any_successful_sinks_should_not_be_stored() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let sinks = Sinks::new(vec![Box::new(TestSink {
+            is_failing: true,
+            events,
+        })]);
+        assert!(!sinks.put(&get_event("a"), &get_logger()));
+    }
+
+    #[test]
+    fn put_should_spool_rejected_events_in_order() {
+        let dir = env::temp_dir().join("nws-api-loader-sink-spool");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+        let spool = Spool::open(
+            dir.join("test.jsonl"),
+            Duration::from_millis(0),
+            Duration::from_millis(0),
+        )
+        .unwrap();
+        let sinks = Sinks {
+            entries: vec![Mutex::new(Entry {
+                sink: Box::new(TestSink {
+                    is_failing: true,
+                    events: events.clone(),
+                }),
+                spool: Some(spool),
+            })],
+        };
+        let logger = get_logger();
+
+        assert!(sinks.put(&get_event("a"), &logger));
+        assert_eq!(vec![("test".to_string(), 1)], sinks.get_spool_depths());
+
+        sinks.entries[0].lock().unwrap().sink = Box::new(TestSink {
+            is_failing: false,
+            events: events.clone(),
+        });
+        assert!(sinks.put(&get_event("b"), &logger));
+        assert_eq!(vec!["a", "b"], *events.lock().unwrap());
+        assert_eq!(vec![("test".to_string(), 0)], sinks.get_spool_depths());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // The NWS details the store gets for an event
+    fn get_stored_details(event: &NwsEvent) -> serde_json::Value {
+        let stored = get_store_event(event).unwrap();
+        serde_json::from_str(&stored.text.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn get_store_event_should_keep_plain_events() {
+        let mut event = get_event("a");
+        event.event.text = Some("text".to_string());
+        let stored = get_store_event(&event).unwrap();
+        assert_eq!(Some("text".to_string()), stored.text);
+    }
+
+    #[test]
+    fn get_store_event_should_include_watch_counties() {
+        let mut event = get_event("a");
+        event.event.text = Some("text".to_string());
+        event.watch_counties = Some(WatchCounties {
+            added: vec!["TXC001".to_string()],
+            ..WatchCounties::default()
+        });
+        let details = get_stored_details(&event);
+        assert_eq!("text", details["text"]);
+        assert_eq!("TXC001", details["watch_counties"]["added"][0]);
+        assert_eq!("a", get_store_event(&event).unwrap().title);
+    }
+
+    #[test]
+    fn get_store_event_should_include_vtec() {
+        let product = get_product_from_file("data/products/tor-normal");
+        let event = tor_parser::parse(&product, Regexes::new())
+            .unwrap()
+            .unwrap();
+        let details = get_stored_details(&event);
+        assert_eq!("TO", details["vtec"]["phenomena"]);
+        assert_eq!("New", details["vtec"]["action"]);
+        assert_eq!(product.product_text, details["text"]);
+    }
+
+    #[test]
+    fn get_store_event_should_include_warning_tags() {
+        let product = get_product_from_file("data/products/tor-normal");
+        let event = tor_parser::parse(&product, Regexes::new())
+            .unwrap()
+            .unwrap();
+        let details = get_stored_details(&event);
+        assert_eq!("RadarIndicated", details["tags"]["tornado"]);
+        assert_eq!(1.25, details["tags"]["max_hail_in"]);
+    }
+
+    #[test]
+    fn get_store_event_should_include_the_outlook_area() {
+        let mut event = get_event("a");
+        event.outlook_area = Some(OutlookArea {
+            hazard: PtsHazard::Tornado,
+            probability: Some(0.1),
+            is_significant: true,
+            risk: None,
+            day: None,
+        });
+        let details = get_stored_details(&event);
+        assert_eq!("Tornado", details["outlook_area"]["hazard"]);
+        assert_eq!(
+            Some(true),
+            details["outlook_area"]["is_significant"].as_bool()
+        );
+        assert!(details["outlook_area"]["probability"].is_number());
+    }
+
+    #[test]
+    fn get_store_event_should_include_the_corrected_report() {
+        let mut event = get_event("a");
+        event.lsr = Some(LsrDetails {
+            id: "KJAN.1555192200000000.TSTM WND DMG.VICKSBURG".to_string(),
+            event_type: LsrEventType::ThunderstormWindDamage,
+            rating: None,
+            corrects: Some("KJAN.1555192200000000.TSTM WND DMG.3 SSW VICKSBURG".to_string()),
+            correction: None,
+        });
+        let details = get_stored_details(&event);
+        assert_eq!(
+            "KJAN.1555192200000000.TSTM WND DMG.3 SSW VICKSBURG",
+            details["lsr"]["corrects"]
+        );
+    }
+
+    #[test]
+    fn file_sink_should_rotate() {
+        let dir = env::temp_dir().join("nws-api-loader-file-sink");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("events.jsonl");
+        let event = get_event("a");
+        let len = serde_json::to_string(&event).unwrap().len() as u64 + 1;
+
+        let mut sink = FileSink::open(path.to_str().unwrap(), len * 2, 2).unwrap();
+        for _ in 0..7 {
+            sink.put(&event).unwrap();
+        }
+
+        let count_lines = |name: &str| {
+            fs::read_to_string(dir.join(name))
+                .map(|x| x.lines().count())
+                .unwrap_or(0)
+        };
+        assert_eq!(1, count_lines("events.jsonl"));
+        assert_eq!(2, count_lines("events.jsonl.1"));
+        assert_eq!(2, count_lines("events.jsonl.2"));
+        assert!(!dir.join("events.jsonl.3").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
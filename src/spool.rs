@@ -0,0 +1,247 @@
+use super::domain::NwsEvent;
+use super::sink::Sink;
+use super::util::get_backoff;
+use slog::Logger;
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use wx::error::Error;
+
+// Most events sent per retry, so draining a backlog doesn't hold the sink's lock for long
+const RETRY_BATCH_SIZE: usize = 100;
+
+/**
+ * Events a sink rejected, kept in order as JSON lines on disk until the sink takes them. Every
+ * event is written (and synced) before it's considered spooled, so they survive a restart.
+ * While anything is spooled, new events queue up behind it rather than jumping ahead.
+ */
+pub struct Spool {
+    path: PathBuf,
+    pending: VecDeque<String>,
+    attempt: u32,
+    next_retry: Instant,
+    retry_base: Duration,
+    retry_max: Duration,
+}
+
+impl Spool {
+    /**
+     * Opens a spool, picking up anything left from before a restart.
+     */
+    pub fn open(path: PathBuf, retry_base: Duration, retry_max: Duration) -> Result<Spool, Error> {
+        let pending = if path.exists() {
+            fs::read_to_string(&path)?
+                .lines()
+                .filter(|x| !x.trim().is_empty())
+                .map(ToString::to_string)
+                .collect()
+        } else {
+            VecDeque::new()
+        };
+
+        Ok(Spool {
+            path,
+            pending,
+            attempt: 0,
+            next_retry: Instant::now(),
+            retry_base,
+            retry_max,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn push(&mut self, event: &NwsEvent) -> Result<(), Error> {
+        let line = serde_json::to_string(event)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        file.sync_data()?;
+        self.pending.push_back(line);
+        Ok(())
+    }
+
+    /**
+     * Sends up to a batch of spooled events to the sink, oldest first, stopping at the first
+     * failure and backing off before the next attempt. Does nothing until the backoff has
+     * passed. A large backlog drains a batch at a time over several calls.
+     */
+    pub fn retry(&mut self, sink: &mut dyn Sink, logger: &Logger) {
+        if self.pending.is_empty() || Instant::now() < self.next_retry {
+            return;
+        }
+
+        let mut delivered = 0;
+        let mut is_failed = false;
+
+        for line in self.pending.iter().take(RETRY_BATCH_SIZE) {
+            let event: NwsEvent = match serde_json::from_str(line) {
+                Ok(value) => value,
+                Err(error) => {
+                    // Retrying won't fix it, so it would block everything behind it forever
+                    error!(logger, "Dropping unreadable spooled event"; "sink" => sink.name(), "error" => format!("{}", error));
+                    delivered += 1;
+                    continue;
+                }
+            };
+
+            match sink.put(&event) {
+                Ok(_) => delivered += 1,
+                Err(error) => {
+                    warn!(logger, "Spool retry failed"; "sink" => sink.name(), "error" => format!("{}", error));
+                    is_failed = true;
+                    break;
+                }
+            }
+        }
+
+        if is_failed {
+            self.next_retry =
+                Instant::now() + get_backoff(self.retry_base, self.retry_max, self.attempt);
+            self.attempt += 1;
+        } else {
+            self.attempt = 0;
+        }
+
+        if delivered > 0 {
+            self.pending.drain(..delivered);
+            info!(logger, "Delivered spooled events"; "sink" => sink.name(), "count" => delivered, "depth" => self.depth());
+            if let Err(error) = self.persist() {
+                error!(logger, "Spool error"; "sink" => sink.name(), "error" => format!("{}", error));
+            }
+        }
+    }
+
+    /**
+     * Rewrites the file with what's left, through a temporary file so a crash can't lose it.
+     */
+    fn persist(&self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)?;
+            }
+            return Ok(());
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let contents: String = self.pending.iter().map(|x| format!("{}\n", x)).collect();
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::{get_event, get_logger};
+    use super::*;
+    use std::env;
+
+    struct FlakySink {
+        failures: usize,
+        titles: Vec<String>,
+    }
+
+    impl Sink for FlakySink {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn put(&mut self, event: &NwsEvent) -> Result<(), Error> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(Error::Wx(<wx::error::WxError>::new("unavailable")));
+            }
+            self.titles.push(event.event.title.to_string());
+            Ok(())
+        }
+    }
+
+    fn get_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn spool_should_survive_a_restart() {
+        let path = get_path("nws-api-loader-spool-restart.jsonl");
+        let mut spool =
+            Spool::open(path.clone(), Duration::from_secs(1), Duration::from_secs(1)).unwrap();
+        spool.push(&get_event("a")).unwrap();
+        spool.push(&get_event("b")).unwrap();
+
+        let mut spool =
+            Spool::open(path.clone(), Duration::from_secs(1), Duration::from_secs(1)).unwrap();
+        assert_eq!(2, spool.depth());
+
+        let logger = get_logger();
+        let mut sink = FlakySink {
+            failures: 0,
+            titles: vec![],
+        };
+        spool.retry(&mut sink, &logger);
+        assert_eq!(vec!["a", "b"], sink.titles);
+        assert_eq!(0, spool.depth());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn retry_should_keep_order_and_back_off() {
+        let path = get_path("nws-api-loader-spool-retry.jsonl");
+        let mut spool = Spool::open(
+            path.clone(),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        spool.push(&get_event("a")).unwrap();
+        spool.push(&get_event("b")).unwrap();
+
+        let logger = get_logger();
+        let mut sink = FlakySink {
+            failures: 1,
+            titles: vec![],
+        };
+        spool.retry(&mut sink, &logger);
+        assert_eq!(2, spool.depth());
+
+        // Still backing off, so the sink isn't tried again yet
+        spool.retry(&mut sink, &logger);
+        assert_eq!(2, spool.depth());
+        assert!(sink.titles.is_empty());
+
+        spool.next_retry = Instant::now();
+        spool.retry(&mut sink, &logger);
+        assert_eq!(vec!["a", "b"], sink.titles);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn retry_should_send_a_batch_at_a_time() {
+        let path = get_path("nws-api-loader-spool-batch.jsonl");
+        let mut spool =
+            Spool::open(path.clone(), Duration::from_secs(1), Duration::from_secs(1)).unwrap();
+        for _ in 0..RETRY_BATCH_SIZE + 1 {
+            spool.push(&get_event("a")).unwrap();
+        }
+
+        let logger = get_logger();
+        let mut sink = FlakySink {
+            failures: 0,
+            titles: vec![],
+        };
+        spool.retry(&mut sink, &logger);
+        assert_eq!(RETRY_BATCH_SIZE, sink.titles.len());
+        assert_eq!(1, spool.depth());
+        spool.retry(&mut sink, &logger);
+        assert_eq!(0, spool.depth());
+    }
+}
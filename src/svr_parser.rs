@@ -1,53 +1,33 @@
-use super::domain::Product;
-use super::parser::{cap, get_parse_error, short_time_to_ticks, str_to_latlon, Regexes};
+use super::domain::{NwsEvent, Product};
+use super::parser::{
+    cap, get_issuance_ticks, get_issued_for, get_poly, parse_cap, str_to_latlon, FieldError,
+    Regexes,
+};
+use super::tags;
+use super::vtec;
 use wx::domain::{Coordinates, Event, EventType, Location, Warning};
-use wx::error::Error;
-use wx::util;
 
 /**
  * Parses an NWS Severe Thunderstorm Warning (SVR).
  */
-pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<NwsEvent>, FieldError> {
     let text = &product.product_text;
     let movement = regexes
         .movement
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let poly_captures = regexes.poly.captures_iter(&text);
-    let source_capture = regexes
-        .source
-        .captures(&text);
-    let lat = str_to_latlon(cap(movement.name("lat")), false);
-    let lon = str_to_latlon(cap(movement.name("lon")), true);
-    let valid_range = regexes
-        .valid
-        .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let issued_for = regexes
-        .warning_for
-        .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let issued_for = issued_for[1]
-        .replace("\n", "")
-        .replace("...", ",")
-        .replace("  ", " ");
-    let issued_for = issued_for.trim();
-    let if_len = issued_for.len() - 1;
-    let issued_for = issued_for[..if_len].to_string();
+        .ok_or_else(|| FieldError::missing("movement"))?;
+    let source_capture = regexes.source.captures(&text);
+    let lat = str_to_latlon(cap(&movement, "lat")?, false)?;
+    let lon = str_to_latlon(cap(&movement, "lon")?, true)?;
+    let vtec = vtec::parse_first(&text, &regexes)?;
+    let issued_for = get_issued_for(&text, &regexes)?;
 
-    let mut poly: Vec<Coordinates> = vec![];
-    for latlon in poly_captures.take(4) {
-        let splits: Vec<&str> = latlon[0].split(' ').collect();
-        poly.push(Coordinates {
-            lat: str_to_latlon(splits[0], false),
-            lon: str_to_latlon(splits[1], true),
-        });
-    }
+    let poly = get_poly(&text, &regexes)?;
 
     let wfo = product.issuing_office.to_string();
-    let valid_ts = Some(short_time_to_ticks(&valid_range[1])?);
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
-    let expires_ts = Some(short_time_to_ticks(&valid_range[2])?);
+    let valid_ts = vtec.begin_ts;
+    let event_ts = get_issuance_ticks(product)?;
+    let expires_ts = vtec.end_ts;
     let title = format!("Severe Thunderstorm Warning ({})", wfo); // 31 chars max
 
     let location = Some(Location {
@@ -60,7 +40,7 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
     let lower_case_text = text.to_lowercase();
 
     let source = match source_capture {
-        Some(val) => Some(cap(val.name("src")).to_string()),
+        Some(val) => Some(cap(&val, "src")?.to_string()),
         None => None,
     };
 
@@ -68,11 +48,11 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
         is_pds: lower_case_text.contains("particularly dangerous situation"),
         was_observed: None,
         is_tor_emergency: None,
-        motion_deg: Some(cap(movement.name("deg")).parse::<u16>()?),
-        motion_kt: Some(cap(movement.name("kt")).parse::<u16>()?),
+        motion_deg: Some(parse_cap::<u16>(&movement, "deg")?),
+        motion_kt: Some(parse_cap::<u16>(&movement, "kt")?),
         source,
         issued_for,
-        time: cap(movement.name("time")).to_string(),
+        time: cap(&movement, "time")?.to_string(),
     });
 
     let event = Event {
@@ -92,7 +72,11 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
         watch: None,
     };
 
-    Ok(Some(event))
+    Ok(Some(NwsEvent {
+        vtec: Some(vtec),
+        tags: tags::parse(&text, &regexes),
+        ..NwsEvent::from(event)
+    }))
 }
 
 #[cfg(test)]
@@ -106,7 +90,7 @@ mod tests {
         let regexes = Regexes::new();
         let result = parse(&product, regexes).unwrap();
         let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1523658960000000,"event_type":"NwsSvr","expires_ts":1523661300000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KDMX","point":{"lat":41.98,"lon":-94.62},"poly":[{"lat":42.21,"lon":-94.75},{"lat":42.21,"lon":-94.34},{"lat":41.91,"lon":-94.52},{"lat":41.91,"lon":-94.75}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n601 \nWUUS53 KDMX 132236\nSVRDMX\nIAC027-073-132315-\n/O.NEW.KDMX.SV.W.0002.180413T2236Z-180413T2315Z/\n\nBULLETIN - IMMEDIATE BROADCAST REQUESTED\nSevere Thunderstorm Warning\nNational Weather Service Des Moines IA\n536 PM CDT FRI APR 13 2018\n\nThe National Weather Service in Des Moines  has issued a\n\n* Severe Thunderstorm Warning for...\n  Western Greene County in west central Iowa...\n  Eastern Carroll County in west central Iowa...\n\n* Until 615 PM CDT.\n\n* At 536 PM CDT, a severe thunderstorm was located 7 miles southeast\n  of Glidden, or 12 miles west of Jefferson, moving northeast at 30\n  mph.\n\n  HAZARD...60 mph wind gusts and quarter size hail. \n\n  SOURCE...Radar indicated. \n\n  IMPACT...Hail damage to vehicles is expected. Expect wind damage \n           to roofs, siding, and trees. \n\n* Locations impacted include...\n  Glidden, Scranton, Churdan, Lanesboro, Ralston and Hobbs County\n  Park.\n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nFor your protection move to an interior room on the lowest floor of a\nbuilding.\n\nTorrential rainfall is occurring with this storm, and may lead to\nflash flooding. Do not drive your vehicle through flooded roadways.\n\n&&\n\nLAT...LON 4221 9475 4221 9434 4191 9452 4191 9475\nTIME...MOT...LOC 2236Z 206DEG 24KT 4198 9462 \n\nHAIL...1.00IN\nWIND...60MPH\n \n$$\n\nMF\n\n","title":"Severe Thunderstorm Warning (KDMX)","valid_ts":1523658960000000,"warning":{"is_pds":false,"is_tor_emergency":null,"was_observed":null,"issued_for":"Western Greene County in west central Iowa, Eastern Carroll County in west central Iowa","motion_deg":206,"motion_kt":24,"source":"Radar indicated","time":"2236Z"},"watch":null}"#;
+        let expected = r#"{"event_ts":1523658960000000,"event_type":"NwsSvr","expires_ts":1523661300000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KDMX","point":{"lat":41.98,"lon":-94.62},"poly":[{"lat":42.21,"lon":-94.75},{"lat":42.21,"lon":-94.34},{"lat":41.91,"lon":-94.52},{"lat":41.91,"lon":-94.75},{"lat":42.21,"lon":-94.75}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n601 \nWUUS53 KDMX 132236\nSVRDMX\nIAC027-073-132315-\n/O.NEW.KDMX.SV.W.0002.180413T2236Z-180413T2315Z/\n\nBULLETIN - IMMEDIATE BROADCAST REQUESTED\nSevere Thunderstorm Warning\nNational Weather Service Des Moines IA\n536 PM CDT FRI APR 13 2018\n\nThe National Weather Service in Des Moines  has issued a\n\n* Severe Thunderstorm Warning for...\n  Western Greene County in west central Iowa...\n  Eastern Carroll County in west central Iowa...\n\n* Until 615 PM CDT.\n\n* At 536 PM CDT, a severe thunderstorm was located 7 miles southeast\n  of Glidden, or 12 miles west of Jefferson, moving northeast at 30\n  mph.\n\n  HAZARD...60 mph wind gusts and quarter size hail. \n\n  SOURCE...Radar indicated. \n\n  IMPACT...Hail damage to vehicles is expected. Expect wind damage \n           to roofs, siding, and trees. \n\n* Locations impacted include...\n  Glidden, Scranton, Churdan, Lanesboro, Ralston and Hobbs County\n  Park.\n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nFor your protection move to an interior room on the lowest floor of a\nbuilding.\n\nTorrential rainfall is occurring with this storm, and may lead to\nflash flooding. Do not drive your vehicle through flooded roadways.\n\n&&\n\nLAT...LON 4221 9475 4221 9434 4191 9452 4191 9475\nTIME...MOT...LOC 2236Z 206DEG 24KT 4198 9462 \n\nHAIL...1.00IN\nWIND...60MPH\n \n$$\n\nMF\n\n","title":"Severe Thunderstorm Warning (KDMX)","valid_ts":1523658960000000,"warning":{"is_pds":false,"is_tor_emergency":null,"was_observed":null,"issued_for":"Western Greene County in west central Iowa, Eastern Carroll County in west central Iowa","motion_deg":206,"motion_kt":24,"source":"Radar indicated","time":"2236Z"},"watch":null,"vtec":{"product_class":"Operational","action":"New","office":"KDMX","phenomena":"SV","significance":"W","etn":2,"begin_ts":1523658960000000,"end_ts":1523661300000000},"tags":{"tornado":null,"tornado_damage_threat":null,"hail":null,"max_hail_in":1.0,"wind":null,"max_wind_mph":60.0,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 }
@@ -1,60 +1,152 @@
-use super::domain::Product;
-use wx::domain::{Event, EventType, Location};
-use wx::error::Error;
-use wx::util;
+use super::domain::{NwsEvent, Product};
+use super::parser::{
+    cap, get_issuance_ticks, get_poly, parse_cap, str_to_latlon, FieldError, Regexes,
+};
+use super::tags;
+use super::ugc::{self, Segment};
+use super::vtec::{self, Action};
+use wx::domain::{Coordinates, Event, EventType, Location, Warning};
 
 /**
- * Parses an NWS Severe Weather Statement (SWS). Primarily used in this API for Tornado Emergency
- * or Particularly Dangerous Situation attribution.
+ * Parses an NWS Severe Weather Statement (SVS). Statements update existing warnings, and can
+ * cancel part of a warning while continuing the rest, so each UGC segment becomes its own
+ * event with its VTEC action, remaining polygon, and latest storm motion. Tornado Emergency
+ * and Particularly Dangerous Situation wording is called out in the title.
  */
-pub fn parse(product: &Product) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Vec<NwsEvent>, FieldError> {
     let text = &product.product_text;
-    let lower_case_text = text.to_lowercase();
-    let is_tor_emergency = lower_case_text.contains("tornado emergency");
-    let is_pds = lower_case_text.contains("particularly dangerous situation");
+    let event_ts = get_issuance_ticks(product)?;
+    let mut events = vec![];
 
-    if !is_tor_emergency && !is_pds {
-        return Ok(None);
+    for segment in ugc::get_segments(&text, &regexes) {
+        let event = parse_segment(product, &segment, event_ts, &regexes)
+            .map_err(|x| x.within(&text, segment.text))?;
+        if let Some(event) = event {
+            events.push(event);
+        }
     }
 
-    let title_fragment = if is_tor_emergency {
+    Ok(events)
+}
+
+fn parse_segment(
+    product: &Product,
+    segment: &Segment,
+    event_ts: u64,
+    regexes: &Regexes,
+) -> Result<Option<NwsEvent>, FieldError> {
+    let wfo = &product.issuing_office;
+    let vtec = match vtec::parse(segment.text, regexes)?.into_iter().next() {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+
+    let lower_case_text = segment.text.to_lowercase();
+    let is_tor_emergency = lower_case_text.contains("tornado emergency");
+    let is_pds = lower_case_text.contains("particularly dangerous situation");
+    let is_tornado = vtec.phenomena == "TO";
+
+    let title_fragment = if is_tornado && is_tor_emergency {
         if is_pds {
-            "PDS Tor Emergency"
+            "PDS Tor Emergency".to_string()
         } else {
-            "Tornado Emergency"
+            "Tornado Emergency".to_string()
+        }
+    } else if is_pds {
+        if is_tornado {
+            "PDS Tornado".to_string()
+        } else {
+            format!("PDS {}", get_warning_name(&vtec.phenomena))
         }
     } else {
-        "PDS Tornado"
+        format!(
+            "{} {}",
+            get_warning_name(&vtec.phenomena),
+            get_action_verb(vtec.action)
+        )
     };
-    let wfo = product.issuing_office.to_string();
     let title = format!("{} SVS: {}", wfo, title_fragment);
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
 
-    let location = Some(Location {
-        point: None,
-        poly: None,
-        wfo: Some(wfo),
+    let (point, motion_deg, motion_kt, time) = match regexes.movement.captures(segment.text) {
+        Some(movement) => (
+            Some(Coordinates {
+                lat: str_to_latlon(cap(&movement, "lat")?, false)?,
+                lon: str_to_latlon(cap(&movement, "lon")?, true)?,
+            }),
+            Some(parse_cap::<u16>(&movement, "deg")?),
+            Some(parse_cap::<u16>(&movement, "kt")?),
+            cap(&movement, "time")?.to_string(),
+        ),
+        None => (None, None, None, "N/A".to_string()),
+    };
+
+    let source = match regexes.source.captures(segment.text) {
+        Some(val) => Some(cap(&val, "src")?.to_string()),
+        None => None,
+    };
+
+    let poly = get_poly(segment.text, regexes)?;
+    let poly = if poly.is_empty() { None } else { Some(poly) };
+
+    let location = Location {
+        wfo: Some(wfo.to_string()),
+        point,
+        poly,
         county: None,
-    });
-
-    let event = Event {
-        event_ts,
-        event_type: EventType::NwsSvs,
-        expires_ts: None,
-        ext_uri: None,
-        ingest_ts: 0,
-        location,
-        md: None,
-        outlook: None,
-        report: None,
-        text: Some(text.to_string()),
-        title,
-        valid_ts: None,
-        warning: None,
-        watch: None,
     };
 
-    Ok(Some(event))
+    let warning = Warning {
+        is_pds,
+        was_observed: if is_tornado {
+            Some(lower_case_text.contains("tornado...observed"))
+        } else {
+            None
+        },
+        is_tor_emergency: if is_tornado {
+            Some(is_tor_emergency)
+        } else {
+            None
+        },
+        motion_deg,
+        motion_kt,
+        source,
+        issued_for: segment.names.join(", "),
+        time,
+    };
+
+    let mut event = Event::new(event_ts, EventType::NwsSvs, title);
+    event.expires_ts = vtec.end_ts;
+    event.location = Some(location);
+    event.text = Some(product.product_text.to_string());
+    event.valid_ts = vtec.begin_ts;
+    event.warning = Some(warning);
+
+    Ok(Some(NwsEvent {
+        vtec: Some(vtec),
+        tags: tags::parse(segment.text, regexes),
+        ..NwsEvent::from(event)
+    }))
+}
+
+fn get_warning_name(phenomena: &str) -> &str {
+    match phenomena {
+        "TO" => "Tornado Warning",
+        "SV" => "Tstm Warning",
+        "FF" => "Flash Flood Warning",
+        _ => "Warning",
+    }
+}
+
+fn get_action_verb(action: Action) -> &'static str {
+    match action {
+        Action::New => "issued",
+        Action::Continued | Action::Routine => "continues",
+        Action::Extended | Action::ExtendedArea | Action::ExtendedBoth => "extended",
+        Action::Upgraded => "upgraded",
+        Action::Cancelled => "cancelled",
+        Action::Expired => "expired",
+        Action::Correction => "corrected",
+    }
 }
 
 #[cfg(test)]
@@ -63,36 +155,87 @@ mod tests {
     use super::*;
 
     #[test]
-    fn parse_svs_product_nothing_interesting() {
+    fn parse_svs_should_emit_an_event_per_segment() {
         let product = get_product_from_file("data/products/svs-tor");
-        let result = parse(&product).unwrap();
-        assert!(result.is_none());
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        let titles: Vec<&str> = result.iter().map(|x| x.event.title.as_str()).collect();
+        let expected = vec![
+            "KAKQ SVS: Tornado Warning cancelled",
+            "KAKQ SVS: Tornado Warning continues",
+        ];
+        assert_eq!(expected, titles);
+
+        let actions: Vec<Action> = result
+            .iter()
+            .map(|x| x.vtec.as_ref().unwrap().action)
+            .collect();
+        assert_eq!(vec![Action::Cancelled, Action::Continued], actions);
+
+        let warning = result[1].event.warning.as_ref().unwrap();
+        let expected = "Camden NC, Gates NC, City of Chesapeake VA, City of Suffolk VA";
+        assert_eq!(expected, warning.issued_for);
+        assert_eq!(Some(196), warning.motion_deg);
+        assert_eq!("0026Z", warning.time);
+    }
+
+    #[test]
+    fn parse_svs_should_keep_segment_polygons() {
+        let product = get_product_from_file("data/products/svs-svr-canceled");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        assert_eq!(2, result.len());
+        assert_eq!("KRNK SVS: Tstm Warning cancelled", result[0].event.title);
+
+        let location = result[0].event.location.as_ref().unwrap();
+        let poly = location.poly.as_ref().unwrap();
+        assert_eq!(16, poly.len());
+        assert_eq!(36.26, poly[14].lat);
+        assert_eq!(-80.04, poly[14].lon);
+        assert_eq!(poly[0].lat, poly[15].lat);
+        assert_eq!(poly[0].lon, poly[15].lon);
+        assert_eq!(None, result[0].event.warning.as_ref().unwrap().was_observed);
     }
 
     #[test]
     fn parse_svs_product_pds() {
         let product = get_product_from_file("data/products/svs-pds-tor");
-        let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":null,"poly":null,"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...A TORNADO WARNING REMAINS IN EFFECT UNTIL 830 PM CDT FOR\nSOUTHEASTERN OTTAWA COUNTY...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\nThis is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: PDS Tornado","valid_ts":null,"warning":null,"watch":null}"#;
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":1525224600000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.06,"lon":-97.69},"poly":[{"lat":39.26,"lon":-97.48},{"lat":38.97,"lon":-97.38},{"lat":38.97,"lon":-97.73},{"lat":39.1,"lon":-97.83},{"lat":39.26,"lon":-97.48}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...A TORNADO WARNING REMAINS IN EFFECT UNTIL 830 PM CDT FOR\nSOUTHEASTERN OTTAWA COUNTY...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\nThis is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: PDS Tornado","valid_ts":null,"warning":{"is_pds":true,"is_tor_emergency":false,"was_observed":true,"issued_for":"Ottawa","motion_deg":244,"motion_kt":27,"source":null,"time":"0107Z"},"watch":null,"vtec":{"product_class":"Operational","action":"Continued","office":"KTOP","phenomena":"TO","significance":"W","etn":8,"begin_ts":null,"end_ts":1525224600000000},"tags":{"tornado":"Observed","tornado_damage_threat":"Considerable","hail":null,"max_hail_in":2.0,"wind":null,"max_wind_mph":null,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
     #[test]
     fn parse_svs_product_tornado_emergency() {
         let product = get_product_from_file("data/products/svs-tor-emergency");
-        let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":null,"poly":null,"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...TORNADO EMERGENCY IN TOPEKA METRO AREA...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\n TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: Tornado Emergency","valid_ts":null,"warning":null,"watch":null}"#;
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":1525224600000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.06,"lon":-97.69},"poly":[{"lat":39.26,"lon":-97.48},{"lat":38.97,"lon":-97.38},{"lat":38.97,"lon":-97.73},{"lat":39.1,"lon":-97.83},{"lat":39.26,"lon":-97.48}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...TORNADO EMERGENCY IN TOPEKA METRO AREA...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\n TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: Tornado Emergency","valid_ts":null,"warning":{"is_pds":false,"is_tor_emergency":true,"was_observed":true,"issued_for":"Ottawa","motion_deg":244,"motion_kt":27,"source":null,"time":"0107Z"},"watch":null,"vtec":{"product_class":"Operational","action":"Continued","office":"KTOP","phenomena":"TO","significance":"W","etn":8,"begin_ts":null,"end_ts":1525224600000000},"tags":{"tornado":"Observed","tornado_damage_threat":"Considerable","hail":null,"max_hail_in":2.0,"wind":null,"max_wind_mph":null,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
+    #[test]
+    fn parse_svs_pds_severe_thunderstorm_should_not_be_a_tornado() {
+        let mut product = get_product_from_file("data/products/svs-pds-tor");
+        product.product_text = product.product_text.replace(".TO.W.", ".SV.W.");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        assert_eq!("KTOP SVS: PDS Tstm Warning", result[0].event.title);
+        let warning = result[0].event.warning.as_ref().unwrap();
+        assert!(warning.is_pds);
+        assert_eq!(None, warning.is_tor_emergency);
+    }
+
     #[test]
     fn parse_svs_both_tornado_emergency_and_pds() {
         let product = get_product_from_file("data/products/svs-pds-tor-emergency");
-        let result = parse(&product).unwrap();
-        let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":null,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":null,"poly":null,"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...A TORNADO EMERGENCY REMAINS IN EFFECT UNTIL 830 PM CDT FOR\nSOUTHEASTERN OTTAWA COUNTY...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\nThis is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: PDS Tor Emergency","valid_ts":null,"warning":null,"watch":null}"#;
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap();
+        let serialized_result = serde_json::to_string(&result[0]).unwrap();
+        let expected = r#"{"event_ts":1525223280000000,"event_type":"NwsSvs","expires_ts":1525224600000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.06,"lon":-97.69},"poly":[{"lat":39.26,"lon":-97.48},{"lat":38.97,"lon":-97.38},{"lat":38.97,"lon":-97.73},{"lat":39.1,"lon":-97.83},{"lat":39.26,"lon":-97.48}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n211 \nWWUS53 KTOP 020108\nSVSTOP\n\nSevere Weather Statement\nNational Weather Service Topeka KS\n808 PM CDT TUE MAY 1 2018\n\nKSC143-020130-\n/O.CON.KTOP.TO.W.0008.000000T0000Z-180502T0130Z/\nOttawa-\n808 PM CDT TUE MAY 1 2018\n\n...A TORNADO EMERGENCY REMAINS IN EFFECT UNTIL 830 PM CDT FOR\nSOUTHEASTERN OTTAWA COUNTY...\n    \nAt 807 PM CDT, a confirmed extremely dangerous tornado was located 4 \nmiles south of Minneapolis, moving northeast at 30 mph. An \nadditional tornado may be forming 5 miles NW of Bennington.\n\nThis is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW!\n\nHAZARD...Damaging tornado. \n\nSOURCE...Law enforcement confirmed tornado. \n\nIMPACT...You are in a life-threatening situation. Flying debris may \n         be deadly to those caught without shelter. Mobile homes \n         will be destroyed. Considerable damage to homes, \n         businesses, and vehicles is likely and complete destruction \n         is possible. \n\nThe tornado will be near...\n  Bennington around 815 PM CDT. \n  Wells around 825 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nHeavy rainfall may hide this tornado. Do not wait to see or hear the\ntornado. TAKE COVER NOW!\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3926 9748 3897 9738 3897 9773 3910 9783\nTIME...MOT...LOC 0107Z 244DEG 27KT 3906 9769 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nSkow\n\n","title":"KTOP SVS: PDS Tor Emergency","valid_ts":null,"warning":{"is_pds":true,"is_tor_emergency":true,"was_observed":true,"issued_for":"Ottawa","motion_deg":244,"motion_kt":27,"source":null,"time":"0107Z"},"watch":null,"vtec":{"product_class":"Operational","action":"Continued","office":"KTOP","phenomena":"TO","significance":"W","etn":8,"begin_ts":null,"end_ts":1525224600000000},"tags":{"tornado":"Observed","tornado_damage_threat":"Considerable","hail":null,"max_hail_in":2.0,"wind":null,"max_wind_mph":null,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 }
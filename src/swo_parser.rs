@@ -1,17 +1,17 @@
 use super::domain::Product;
-use super::parser::{get_parse_error, str_to_latlon, Regexes};
+use super::parser::{
+    cap, get_condensed_coordinates, get_issuance_ticks, get_offset, parse_cap, FieldError, Regexes,
+};
 use wx::domain::{
     Coordinates, Event, EventType, Location, MdConcerning, MesoscaleDiscussion, Outlook,
     OutlookRisk, SwoType,
 };
-use wx::error::{Error, WxError};
-use wx::util;
 
 /**
  * Parses an NWS Severe Storm Outlook Narrative (SWO) product, which includes SPC Outlooks
  * and Mesoscale Discussions (MDs).
  */
-pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, FieldError> {
     let swo_type = get_swo_type(&product.product_text);
 
     match swo_type {
@@ -20,7 +20,7 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
         SwoType::Day3 => Ok(None),
         SwoType::Day48 => Ok(None),
         SwoType::MesoscaleDiscussion => parse_md(product, regexes),
-        SwoType::Unknown => Err(Error::Wx(<WxError>::new("Unknown SWO type"))),
+        SwoType::Unknown => Err(FieldError::missing("SWO type")),
     }
 }
 
@@ -40,10 +40,10 @@ fn get_swo_type(text: &str) -> SwoType {
     }
 }
 
-fn parse_outlook(product: &Product, swo_type: SwoType) -> Result<Option<Event>, Error> {
+fn parse_outlook(product: &Product, swo_type: SwoType) -> Result<Option<Event>, FieldError> {
     let max_risk = get_outlook_risk(&product.product_text);
     let title = format!("SPC {:?} Outlook: {:?}", swo_type, max_risk);
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
+    let event_ts = get_issuance_ticks(product)?;
 
     let outlook = Outlook {
         swo_type,
@@ -71,40 +71,37 @@ fn parse_outlook(product: &Product, swo_type: SwoType) -> Result<Option<Event>,
     Ok(Some(event))
 }
 
-fn parse_md(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error> {
+fn parse_md(product: &Product, regexes: Regexes) -> Result<Option<Event>, FieldError> {
     let text = &product.product_text;
     let id = regexes
         .md_number
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
+        .ok_or_else(|| FieldError::missing("MD number"))?;
     let watch_issuance_probability = regexes.probability.captures(&text);
     let affected = regexes
         .affected
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
+        .ok_or_else(|| FieldError::missing("areas affected"))?;
     let wfos = regexes
         .wfos
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
+        .ok_or_else(|| FieldError::missing("ATTN WFOs"))?;
     let poly_captures = regexes.poly_condensed.captures_iter(&text);
 
     let mut poly: Vec<Coordinates> = vec![];
     for latlon in poly_captures {
-        poly.push(Coordinates {
-            lat: str_to_latlon(&latlon[0][0..4], false),
-            lon: str_to_latlon(&latlon[0][4..8], true),
-        });
+        let latlon = cap(&latlon, "point")?;
+        poly.push(get_condensed_coordinates(latlon).map_err(|x| x.at(get_offset(&text, latlon)))?);
     }
 
-    let id = id[1].parse::<u16>()?;
-    let watch_issuance_probability = if watch_issuance_probability.is_some() {
-        Some(watch_issuance_probability.unwrap()[1].parse::<u16>()?)
-    } else {
-        None
+    let id = parse_cap::<u16>(&id, "id")?;
+    let watch_issuance_probability = match watch_issuance_probability {
+        Some(value) => Some(parse_cap::<u16>(&value, "probability")?),
+        None => None,
     };
     let mut concerning = MdConcerning::Unknown;
-    let affected = affected[1].to_string().replace('\n', " ");
-    let wfos: Vec<String> = wfos[1]
+    let affected = cap(&affected, "affected")?.replace('\n', " ");
+    let wfos: Vec<String> = cap(&wfos, "wfos")?
         .split("...")
         .map(ToString::to_string)
         .filter(|s| s != "")
@@ -114,13 +111,13 @@ fn parse_md(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error>
         concerning = MdConcerning::NewSvrWatch;
         format!(
             "SPC MD: Tstm Watch {:?}%",
-            watch_issuance_probability.unwrap()
+            get_probability(watch_issuance_probability)?
         )
     } else if text.contains("Concerning...Severe potential...Tornado Watch") {
         concerning = MdConcerning::NewTorWatch;
         format!(
             "SPC MD: Tornado Watch {:?}%",
-            watch_issuance_probability.unwrap()
+            get_probability(watch_issuance_probability)?
         )
     } else if text.contains("Concerning...Severe Thunderstorm Watch") {
         concerning = MdConcerning::ExistingSvrWatch;
@@ -140,7 +137,7 @@ fn parse_md(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error>
         wfos,
     };
 
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
+    let event_ts = get_issuance_ticks(product)?;
 
     let location = Some(Location {
         wfo: None,
@@ -169,6 +166,11 @@ fn parse_md(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error>
     Ok(Some(event))
 }
 
+// MDs about a possible new watch are expected to say how likely it is
+fn get_probability(probability: Option<u16>) -> Result<u16, FieldError> {
+    probability.ok_or_else(|| FieldError::missing("probability"))
+}
+
 /**
  * All risks are listed in a text, so we need to exit early when we find the first
  * match by severity descending.
@@ -0,0 +1,164 @@
+use super::parser::Regexes;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Detection {
+    RadarIndicated,
+    Observed,
+    Possible,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DamageThreat {
+    Considerable,
+    Destructive,
+    Catastrophic,
+}
+
+/**
+ * Impact-based warning tags, which follow the polygon in warnings and statements, ex.
+ * `TORNADO...OBSERVED` and `TORNADO DAMAGE THREAT...CONSIDERABLE`. Hail sizes are in inches
+ * and wind gusts in mph. A size given as `<.75IN` (below severe criteria) is kept as 0.75.
+ */
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct WarningTags {
+    pub tornado: Option<Detection>,
+    pub tornado_damage_threat: Option<DamageThreat>,
+    pub hail: Option<Detection>,
+    pub max_hail_in: Option<f32>,
+    pub wind: Option<Detection>,
+    pub max_wind_mph: Option<f32>,
+    pub thunderstorm_damage_threat: Option<DamageThreat>,
+    pub flash_flood: Option<Detection>,
+    pub flash_flood_damage_threat: Option<DamageThreat>,
+}
+
+/**
+ * Reads the tags out of a warning or a single statement segment. Older products that predate
+ * the tags return None. Some products repeat their text, so the first value for a tag wins.
+ */
+pub fn parse(text: &str, regexes: &Regexes) -> Option<WarningTags> {
+    let mut tags = WarningTags::default();
+
+    for captures in regexes.tag.captures_iter(text) {
+        let (key, value) = match (captures.name("key"), captures.name("value")) {
+            (Some(key), Some(value)) => {
+                (key.as_str().to_uppercase(), value.as_str().to_uppercase())
+            }
+            _ => continue,
+        };
+
+        match key.as_str() {
+            "TORNADO" => set(&mut tags.tornado, get_detection(&value)),
+            "TORNADO DAMAGE THREAT" => {
+                set(&mut tags.tornado_damage_threat, get_damage_threat(&value))
+            }
+            "HAIL THREAT" => set(&mut tags.hail, get_detection(&value)),
+            "HAIL" | "MAX HAIL SIZE" => set(&mut tags.max_hail_in, get_measurement(&value, "IN")),
+            "WIND THREAT" => set(&mut tags.wind, get_detection(&value)),
+            "WIND" | "MAX WIND GUST" => set(&mut tags.max_wind_mph, get_measurement(&value, "MPH")),
+            "THUNDERSTORM DAMAGE THREAT" => set(
+                &mut tags.thunderstorm_damage_threat,
+                get_damage_threat(&value),
+            ),
+            "FLASH FLOOD" => set(&mut tags.flash_flood, get_detection(&value)),
+            "FLASH FLOOD DAMAGE THREAT" => set(
+                &mut tags.flash_flood_damage_threat,
+                get_damage_threat(&value),
+            ),
+            _ => (),
+        }
+    }
+
+    if tags == WarningTags::default() {
+        None
+    } else {
+        Some(tags)
+    }
+}
+
+fn set<T>(field: &mut Option<T>, value: Option<T>) {
+    if field.is_none() {
+        *field = value;
+    }
+}
+
+fn get_detection(input: &str) -> Option<Detection> {
+    match input {
+        "RADAR INDICATED" => Some(Detection::RadarIndicated),
+        "OBSERVED" => Some(Detection::Observed),
+        "POSSIBLE" => Some(Detection::Possible),
+        _ => None,
+    }
+}
+
+fn get_damage_threat(input: &str) -> Option<DamageThreat> {
+    match input {
+        "CONSIDERABLE" => Some(DamageThreat::Considerable),
+        "DESTRUCTIVE" => Some(DamageThreat::Destructive),
+        "CATASTROPHIC" => Some(DamageThreat::Catastrophic),
+        _ => None,
+    }
+}
+
+// ex. `1.25IN`, `<.75IN`, `1.00 IN`, or `60 MPH`
+fn get_measurement(input: &str, units: &str) -> Option<f32> {
+    input
+        .trim_start_matches('<')
+        .trim_end_matches(units)
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_read_tornado_tags() {
+        let text = "LAT...LON 3642 7666 3670 7661 3667 7631\nTIME...MOT...LOC 0026Z 196DEG 43KT 3646 7659\n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n";
+        let regexes = Regexes::new();
+        let result = parse(text, &regexes).unwrap();
+        assert_eq!(Some(Detection::Observed), result.tornado);
+        assert_eq!(
+            Some(DamageThreat::Considerable),
+            result.tornado_damage_threat
+        );
+        assert_eq!(Some(2.0), result.max_hail_in);
+        assert_eq!(None, result.max_wind_mph);
+    }
+
+    #[test]
+    fn parse_should_read_threat_tags() {
+        let text = "HAIL THREAT...RADAR INDICATED\nMAX HAIL SIZE...<.75 IN\n\nWIND THREAT...OBSERVED\nMAX WIND GUST...70 MPH\n\nTHUNDERSTORM DAMAGE THREAT...DESTRUCTIVE\n";
+        let regexes = Regexes::new();
+        let result = parse(text, &regexes).unwrap();
+        assert_eq!(Some(Detection::RadarIndicated), result.hail);
+        assert_eq!(Some(0.75), result.max_hail_in);
+        assert_eq!(Some(Detection::Observed), result.wind);
+        assert_eq!(Some(70.0), result.max_wind_mph);
+        assert_eq!(
+            Some(DamageThreat::Destructive),
+            result.thunderstorm_damage_threat
+        );
+    }
+
+    #[test]
+    fn parse_should_read_flash_flood_tags() {
+        let text = "FLASH FLOOD...OBSERVED\nFLASH FLOOD DAMAGE THREAT...CATASTROPHIC\n";
+        let regexes = Regexes::new();
+        let result = parse(text, &regexes).unwrap();
+        assert_eq!(Some(Detection::Observed), result.flash_flood);
+        assert_eq!(
+            Some(DamageThreat::Catastrophic),
+            result.flash_flood_damage_threat
+        );
+    }
+
+    #[test]
+    fn parse_without_tags_should_be_none() {
+        let text = "HAZARD...Tornado.\n\nSOURCE...Radar indicated rotation.\n";
+        let regexes = Regexes::new();
+        assert_eq!(None, parse(text, &regexes));
+    }
+}
@@ -1,6 +1,10 @@
-use super::domain::Product;
+use super::config::HttpConfig;
+use super::domain::{NwsEvent, Product};
+use super::util::Fetcher;
+use slog::Logger;
 use std::fs::File;
 use std::io::Read;
+use wx::domain::{Event, EventType};
 
 #[allow(dead_code)]
 pub fn get_product_from_file(path: &str) -> Product {
@@ -11,3 +15,22 @@ pub fn get_product_from_file(path: &str) -> Product {
     let product: Product = serde_json::from_str(&contents).unwrap();
     product
 }
+
+#[allow(dead_code)]
+pub fn get_event(title: &str) -> NwsEvent {
+    NwsEvent::from(Event::new(
+        1555977180000000,
+        EventType::NwsTor,
+        title.to_string(),
+    ))
+}
+
+#[allow(dead_code)]
+pub fn get_logger() -> Logger {
+    Logger::root(slog::Discard, o!())
+}
+
+#[allow(dead_code)]
+pub fn get_fetcher(config: &HttpConfig) -> Fetcher {
+    Fetcher::new(&get_logger(), "test", config).unwrap()
+}
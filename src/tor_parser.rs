@@ -1,51 +1,33 @@
-use super::domain::Product;
-use super::parser::{cap, get_parse_error, short_time_to_ticks, str_to_latlon, Regexes};
+use super::domain::{NwsEvent, Product};
+use super::parser::{
+    cap, get_issuance_ticks, get_issued_for, get_poly, parse_cap, str_to_latlon, FieldError,
+    Regexes,
+};
+use super::tags;
+use super::vtec;
 use wx::domain::{Coordinates, Event, EventType, Location, Warning};
-use wx::error::Error;
-use wx::util;
 
-pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error> {
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<NwsEvent>, FieldError> {
     let text = &product.product_text;
     let movement = regexes
         .movement
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let poly_captures = regexes.poly.captures_iter(&text);
+        .ok_or_else(|| FieldError::missing("movement"))?;
     let source = regexes
         .source
         .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let lat = str_to_latlon(cap(movement.name("lat")), false);
-    let lon = str_to_latlon(cap(movement.name("lon")), true);
-    let valid_range = regexes
-        .valid
-        .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let issued_for = regexes
-        .warning_for
-        .captures(&text)
-        .ok_or_else(|| get_parse_error(&text))?;
-    let issued_for = issued_for[1]
-        .replace("\n", "")
-        .replace("...", ",")
-        .replace("  ", " ");
-    let issued_for = issued_for.trim();
-    let if_len = issued_for.len() - 1;
-    let issued_for = issued_for[..if_len].to_string();
-
-    let mut poly: Vec<Coordinates> = vec![];
-    for latlon in poly_captures.take(4) {
-        let splits: Vec<&str> = latlon[0].split(' ').collect();
-        poly.push(Coordinates {
-            lat: str_to_latlon(splits[0], false),
-            lon: str_to_latlon(splits[1], true),
-        });
-    }
+        .ok_or_else(|| FieldError::missing("source"))?;
+    let lat = str_to_latlon(cap(&movement, "lat")?, false)?;
+    let lon = str_to_latlon(cap(&movement, "lon")?, true)?;
+    let vtec = vtec::parse_first(&text, &regexes)?;
+    let issued_for = get_issued_for(&text, &regexes)?;
+
+    let poly = get_poly(&text, &regexes)?;
 
     let wfo = product.issuing_office.to_string();
-    let valid_ts = Some(short_time_to_ticks(&valid_range[1])?);
-    let event_ts = util::ts_to_ticks(&product.issuance_time)?;
-    let expires_ts = Some(short_time_to_ticks(&valid_range[2])?);
+    let valid_ts = vtec.begin_ts;
+    let event_ts = get_issuance_ticks(product)?;
+    let expires_ts = vtec.end_ts;
     let title = format!("Tornado Warning ({})", wfo);
 
     let location = Some(Location {
@@ -61,11 +43,11 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
         is_pds: lower_case_text.contains("particularly dangerous situation"),
         was_observed: Some(lower_case_text.contains("tornado...observed")),
         is_tor_emergency: Some(lower_case_text.contains("tornado emergency")),
-        motion_deg: Some(cap(movement.name("deg")).parse::<u16>()?),
-        motion_kt: Some(cap(movement.name("kt")).parse::<u16>()?),
-        source: Some(cap(source.name("src")).to_string()),
+        motion_deg: Some(parse_cap::<u16>(&movement, "deg")?),
+        motion_kt: Some(parse_cap::<u16>(&movement, "kt")?),
+        source: Some(cap(&source, "src")?.to_string()),
         issued_for,
-        time: cap(movement.name("time")).to_string(),
+        time: cap(&movement, "time")?.to_string(),
     });
 
     let event = Event {
@@ -85,7 +67,11 @@ pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<Event>, Error
         watch: None,
     };
 
-    Ok(Some(event))
+    Ok(Some(NwsEvent {
+        vtec: Some(vtec),
+        tags: tags::parse(&text, &regexes),
+        ..NwsEvent::from(event)
+    }))
 }
 
 #[cfg(test)]
@@ -109,7 +95,7 @@ mod tests {
         let regexes = Regexes::new();
         let result = parse(&product, regexes).unwrap();
         let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1525222860000000,"event_type":"NwsTor","expires_ts":1525225500000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.52,"lon":-97.28},"poly":[{"lat":39.77,"lon":-96.97},{"lat":39.5,"lon":-96.8},{"lat":39.39,"lon":-97.37},{"lat":39.59,"lon":-97.37}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n271 \nWFUS53 KTOP 020101\nTORTOP\nKSC027-161-201-020145-\n/O.NEW.KTOP.TO.W.0009.180502T0101Z-180502T0145Z/\n\nBULLETIN - EAS ACTIVATION REQUESTED\nTornado Warning\nNational Weather Service Topeka KS\n801 PM CDT TUE MAY 1 2018\n\nThe National Weather Service in Topeka has issued a\n\n* Tornado Warning for...\n  Northwestern Riley County in northeastern Kansas...\n  Southern Washington County in north central Kansas...\n  Northern Clay County in north central Kansas...\n\n* Until 845 PM CDT\n    \n* At 800 PM CDT, a large and extremely dangerous tornado was located\n  2 miles south of Clifton, moving northeast at 25 mph.\n\n  TAKE COVER NOW! \n\n  HAZARD...Damaging tornado. \n\n  SOURCE...Radar indicated rotation. \n\n  IMPACT...You are in a life-threatening situation. Flying debris \n           may be deadly to those caught without shelter. Mobile \n           homes will be destroyed. Considerable damage to homes, \n           businesses, and vehicles is likely and complete \n           destruction is possible. \n\n* The tornado will be near...\n  Morganville around 805 PM CDT. \n  Palmer around 820 PM CDT. \n  Linn around 830 PM CDT. \n  Greenleaf around 845 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nTo repeat, a large, extremely dangerous and potentially deadly\ntornado is developing. To protect your life, TAKE COVER NOW! Move to\na basement or an interior room on the lowest floor of a sturdy\nbuilding. Avoid windows. If you are outdoors, in a mobile home, or in\na vehicle, move to the closest substantial shelter and protect\nyourself from flying debris.\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3977 9697 3950 9680 3939 9737 3959 9737\nTIME...MOT...LOC 0100Z 245DEG 24KT 3952 9728 \n\nTORNADO...RADAR INDICATED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nBaerg\n\n","title":"Tornado Warning (KTOP)","valid_ts":1525222860000000,"warning":{"is_pds":false,"is_tor_emergency":false,"was_observed":false,"issued_for":"Northwestern Riley County in northeastern Kansas, Southern Washington County in north central Kansas, Northern Clay County in north central Kansas","motion_deg":245,"motion_kt":24,"source":"Radar indicated rotation","time":"0100Z"},"watch":null}"#;
+        let expected = r#"{"event_ts":1525222860000000,"event_type":"NwsTor","expires_ts":1525225500000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.52,"lon":-97.28},"poly":[{"lat":39.77,"lon":-96.97},{"lat":39.5,"lon":-96.8},{"lat":39.39,"lon":-97.37},{"lat":39.59,"lon":-97.37},{"lat":39.77,"lon":-96.97}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n271 \nWFUS53 KTOP 020101\nTORTOP\nKSC027-161-201-020145-\n/O.NEW.KTOP.TO.W.0009.180502T0101Z-180502T0145Z/\n\nBULLETIN - EAS ACTIVATION REQUESTED\nTornado Warning\nNational Weather Service Topeka KS\n801 PM CDT TUE MAY 1 2018\n\nThe National Weather Service in Topeka has issued a\n\n* Tornado Warning for...\n  Northwestern Riley County in northeastern Kansas...\n  Southern Washington County in north central Kansas...\n  Northern Clay County in north central Kansas...\n\n* Until 845 PM CDT\n    \n* At 800 PM CDT, a large and extremely dangerous tornado was located\n  2 miles south of Clifton, moving northeast at 25 mph.\n\n  TAKE COVER NOW! \n\n  HAZARD...Damaging tornado. \n\n  SOURCE...Radar indicated rotation. \n\n  IMPACT...You are in a life-threatening situation. Flying debris \n           may be deadly to those caught without shelter. Mobile \n           homes will be destroyed. Considerable damage to homes, \n           businesses, and vehicles is likely and complete \n           destruction is possible. \n\n* The tornado will be near...\n  Morganville around 805 PM CDT. \n  Palmer around 820 PM CDT. \n  Linn around 830 PM CDT. \n  Greenleaf around 845 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nTo repeat, a large, extremely dangerous and potentially deadly\ntornado is developing. To protect your life, TAKE COVER NOW! Move to\na basement or an interior room on the lowest floor of a sturdy\nbuilding. Avoid windows. If you are outdoors, in a mobile home, or in\na vehicle, move to the closest substantial shelter and protect\nyourself from flying debris.\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3977 9697 3950 9680 3939 9737 3959 9737\nTIME...MOT...LOC 0100Z 245DEG 24KT 3952 9728 \n\nTORNADO...RADAR INDICATED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nBaerg\n\n","title":"Tornado Warning (KTOP)","valid_ts":1525222860000000,"warning":{"is_pds":false,"is_tor_emergency":false,"was_observed":false,"issued_for":"Northwestern Riley County in northeastern Kansas, Southern Washington County in north central Kansas, Northern Clay County in north central Kansas","motion_deg":245,"motion_kt":24,"source":"Radar indicated rotation","time":"0100Z"},"watch":null,"vtec":{"product_class":"Operational","action":"New","office":"KTOP","phenomena":"TO","significance":"W","etn":9,"begin_ts":1525222860000000,"end_ts":1525225500000000},"tags":{"tornado":"RadarIndicated","tornado_damage_threat":"Considerable","hail":null,"max_hail_in":2.0,"wind":null,"max_wind_mph":null,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
@@ -129,7 +115,7 @@ mod tests {
         let regexes = Regexes::new();
         let result = parse(&product, regexes).unwrap();
         let serialized_result = serde_json::to_string(&result).unwrap();
-        let expected = r#"{"event_ts":1525222860000000,"event_type":"NwsTor","expires_ts":1525225500000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.52,"lon":-97.28},"poly":[{"lat":39.77,"lon":-96.97},{"lat":39.5,"lon":-96.8},{"lat":39.39,"lon":-97.37},{"lat":39.59,"lon":-97.37}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n271 \nWFUS53 KTOP 020101\nTORTOP\nKSC027-161-201-020145-\n/O.NEW.KTOP.TO.W.0009.180502T0101Z-180502T0145Z/\n\nBULLETIN - EAS ACTIVATION REQUESTED\nTornado Warning\nNational Weather Service Topeka KS\n801 PM CDT TUE MAY 1 2018\n\nThe National Weather Service in Topeka has issued a\n\n* Tornado Warning for...\n  Northwestern Riley County in northeastern Kansas...\n  Southern Washington County in north central Kansas...\n  Northern Clay County in north central Kansas...\n\n* Until 845 PM CDT\n    \n* At 800 PM CDT, a large and extremely dangerous tornado was located\n  2 miles south of Clifton, moving northeast at 25 mph.\n\n  THIS IS A TORNADO EMERGENCY FOR CLIFTON. \n\n This is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW! \n\n  HAZARD...Damaging tornado. \n\n  SOURCE...Radar indicated rotation. \n\n  IMPACT...You are in a life-threatening situation. Flying debris \n           may be deadly to those caught without shelter. Mobile \n           homes will be destroyed. Considerable damage to homes, \n           businesses, and vehicles is likely and complete \n           destruction is possible. \n\n* The tornado will be near...\n  Morganville around 805 PM CDT. \n  Palmer around 820 PM CDT. \n  Linn around 830 PM CDT. \n  Greenleaf around 845 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nTo repeat, a large, extremely dangerous and potentially deadly\ntornado is developing. To protect your life, TAKE COVER NOW! Move to\na basement or an interior room on the lowest floor of a sturdy\nbuilding. Avoid windows. If you are outdoors, in a mobile home, or in\na vehicle, move to the closest substantial shelter and protect\nyourself from flying debris.\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3977 9697 3950 9680 3939 9737 3959 9737\nTIME...MOT...LOC 0100Z 245DEG 24KT 3952 9728 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nBaerg\n\n","title":"Tornado Warning (KTOP)","valid_ts":1525222860000000,"warning":{"is_pds":true,"is_tor_emergency":true,"was_observed":true,"issued_for":"Northwestern Riley County in northeastern Kansas, Southern Washington County in north central Kansas, Northern Clay County in north central Kansas","motion_deg":245,"motion_kt":24,"source":"Radar indicated rotation","time":"0100Z"},"watch":null}"#;
+        let expected = r#"{"event_ts":1525222860000000,"event_type":"NwsTor","expires_ts":1525225500000000,"ext_uri":null,"ingest_ts":0,"location":{"wfo":"KTOP","point":{"lat":39.52,"lon":-97.28},"poly":[{"lat":39.77,"lon":-96.97},{"lat":39.5,"lon":-96.8},{"lat":39.39,"lon":-97.37},{"lat":39.59,"lon":-97.37},{"lat":39.77,"lon":-96.97}],"county":null},"md":null,"outlook":null,"report":null,"text":"\n271 \nWFUS53 KTOP 020101\nTORTOP\nKSC027-161-201-020145-\n/O.NEW.KTOP.TO.W.0009.180502T0101Z-180502T0145Z/\n\nBULLETIN - EAS ACTIVATION REQUESTED\nTornado Warning\nNational Weather Service Topeka KS\n801 PM CDT TUE MAY 1 2018\n\nThe National Weather Service in Topeka has issued a\n\n* Tornado Warning for...\n  Northwestern Riley County in northeastern Kansas...\n  Southern Washington County in north central Kansas...\n  Northern Clay County in north central Kansas...\n\n* Until 845 PM CDT\n    \n* At 800 PM CDT, a large and extremely dangerous tornado was located\n  2 miles south of Clifton, moving northeast at 25 mph.\n\n  THIS IS A TORNADO EMERGENCY FOR CLIFTON. \n\n This is a PARTICULARLY DANGEROUS SITUATION. TAKE COVER NOW! \n\n  HAZARD...Damaging tornado. \n\n  SOURCE...Radar indicated rotation. \n\n  IMPACT...You are in a life-threatening situation. Flying debris \n           may be deadly to those caught without shelter. Mobile \n           homes will be destroyed. Considerable damage to homes, \n           businesses, and vehicles is likely and complete \n           destruction is possible. \n\n* The tornado will be near...\n  Morganville around 805 PM CDT. \n  Palmer around 820 PM CDT. \n  Linn around 830 PM CDT. \n  Greenleaf around 845 PM CDT. \n\nPRECAUTIONARY/PREPAREDNESS ACTIONS...\n\nTo repeat, a large, extremely dangerous and potentially deadly\ntornado is developing. To protect your life, TAKE COVER NOW! Move to\na basement or an interior room on the lowest floor of a sturdy\nbuilding. Avoid windows. If you are outdoors, in a mobile home, or in\na vehicle, move to the closest substantial shelter and protect\nyourself from flying debris.\n\nTornadoes are extremely difficult to see and confirm at night. Do not\nwait to see or hear the tornado. TAKE COVER NOW!\n\n&&\n\nLAT...LON 3977 9697 3950 9680 3939 9737 3959 9737\nTIME...MOT...LOC 0100Z 245DEG 24KT 3952 9728 \n\nTORNADO...OBSERVED\nTORNADO DAMAGE THREAT...CONSIDERABLE\nHAIL...2.00IN\n\n$$\n\nBaerg\n\n","title":"Tornado Warning (KTOP)","valid_ts":1525222860000000,"warning":{"is_pds":true,"is_tor_emergency":true,"was_observed":true,"issued_for":"Northwestern Riley County in northeastern Kansas, Southern Washington County in north central Kansas, Northern Clay County in north central Kansas","motion_deg":245,"motion_kt":24,"source":"Radar indicated rotation","time":"0100Z"},"watch":null,"vtec":{"product_class":"Operational","action":"New","office":"KTOP","phenomena":"TO","significance":"W","etn":9,"begin_ts":1525222860000000,"end_ts":1525225500000000},"tags":{"tornado":"Observed","tornado_damage_threat":"Considerable","hail":null,"max_hail_in":2.0,"wind":null,"max_wind_mph":null,"thunderstorm_damage_threat":null,"flash_flood":null,"flash_flood_damage_threat":null}}"#;
         assert_eq!(expected, serialized_result);
     }
 
@@ -148,4 +134,18 @@ mod tests {
         let result = parse(&product, regexes);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn parse_tor_should_keep_every_vertex() {
+        let product = get_product_from_file("data/products/tor-radar-observed");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap().unwrap();
+        let location = result.event.location.unwrap();
+        let poly = location.poly.unwrap();
+        assert_eq!(12, poly.len());
+        assert_eq!(32.96, poly[10].lat);
+        assert_eq!(-92.08, poly[10].lon);
+        assert_eq!(32.98, poly[11].lat);
+        assert_eq!(-92.08, poly[11].lon);
+    }
 }
@@ -0,0 +1,125 @@
+use super::parser::Regexes;
+
+/**
+ * A single `$$` delimited segment of a product that starts with a UGC (Universal Geographic
+ * Code) block. Watch and statement products repeat this layout for each area they cover.
+ */
+#[derive(Debug)]
+pub struct Segment<'a> {
+    pub ugcs: Vec<String>,
+    pub names: Vec<String>,
+    pub text: &'a str,
+}
+
+/**
+ * Splits a product into its UGC segments, skipping any text (headers, ATTN lines) that
+ * isn't part of one.
+ */
+pub fn get_segments<'a>(text: &'a str, regexes: &Regexes) -> Vec<Segment<'a>> {
+    text.split("$$")
+        .filter_map(|segment| {
+            let ugc = regexes.ugc.find(segment)?;
+            Some(Segment {
+                ugcs: expand(ugc.as_str()),
+                names: get_names(&segment[ugc.end()..]),
+                text: segment,
+            })
+        })
+        .collect()
+}
+
+/**
+ * Warning and statement segments list the names of their UGCs after the VTEC line(s), ex.
+ * `Camden NC-Gates NC-City of Chesapeake VA-`, wrapping as needed until the issuance time.
+ * Watch products go straight into the body instead, so they have no names.
+ */
+fn get_names(text: &str) -> Vec<String> {
+    // Skip the remainder of the line the UGC block ends on
+    text.lines()
+        .skip(1)
+        .map(str::trim)
+        .skip_while(|x| x.starts_with('/'))
+        .take_while(|x| !x.is_empty() && !x.starts_with(|c: char| c.is_ascii_digit()))
+        .collect::<Vec<&str>>()
+        .concat()
+        .split('-')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(ToString::to_string)
+        .collect()
+}
+
+/**
+ * Expands a UGC block like `INC005-011>013-KYC091-040000-` into individual codes. The state
+ * and type prefix carries forward until a new one is given, `>` denotes an inclusive range,
+ * and the trailing `ddhhmm` group is the segment expiration rather than a code.
+ */
+pub fn expand(input: &str) -> Vec<String> {
+    let mut ugcs = vec![];
+    let mut prefix = "";
+    let tokens = input
+        .split(|x| x == '-' || x == '\n')
+        .map(str::trim)
+        .filter(|x| !x.is_empty());
+
+    for token in tokens {
+        if token.len() == 6 && token.chars().all(|x| x.is_ascii_digit()) {
+            continue;
+        }
+
+        let code = if token.starts_with(|x: char| x.is_ascii_alphabetic()) {
+            prefix = token.get(0..3).unwrap_or("");
+            token.get(3..).unwrap_or("")
+        } else {
+            token
+        };
+
+        let bounds: Vec<&str> = code.split('>').collect();
+        if let [start, end] = bounds.as_slice() {
+            if let (Ok(start), Ok(end)) = (start.parse::<u16>(), end.parse::<u16>()) {
+                for i in start..=end {
+                    ugcs.push(format!("{}{:03}", prefix, i));
+                }
+            }
+        } else {
+            ugcs.push(format!("{}{}", prefix, code));
+        }
+    }
+
+    ugcs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_should_carry_prefixes_and_ranges() {
+        let result = expand("INC005-011>013-\nKYC091-040000-");
+        let expected = vec!["INC005", "INC011", "INC012", "INC013", "KYC091"];
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn get_segments_should_skip_text_without_ugcs() {
+        let text = "\nWOUS64 KWNS 031713\n\nINC005-011-040000-\n/O.NEW.KWNS.TO.A.0026.180403T1715Z-180404T0000Z/\n$$\n\nKYC015-040000-\n$$\n\nATTN...WFO...IND...\n";
+        let regexes = Regexes::new();
+        let result = get_segments(text, &regexes);
+        let ugcs: Vec<Vec<String>> = result.into_iter().map(|x| x.ugcs).collect();
+        assert_eq!(vec![vec!["INC005", "INC011"], vec!["KYC015"]], ugcs);
+    }
+
+    #[test]
+    fn get_segments_should_read_wrapped_names() {
+        let text = "NCC157-169-VAC089-152115-\n/O.CON.KRNK.SV.W.0011.000000T0000Z-180415T2115Z/\nSurry NC-Stokes NC-City of Martinsville VA-\nPatrick VA-\n439 PM EDT SUN APR 15 2018\n\n...A SEVERE THUNDERSTORM WARNING REMAINS IN EFFECT...\n";
+        let regexes = Regexes::new();
+        let result = get_segments(text, &regexes);
+        let expected = vec![
+            "Surry NC",
+            "Stokes NC",
+            "City of Martinsville VA",
+            "Patrick VA",
+        ];
+        assert_eq!(expected, result[0].names);
+    }
+}
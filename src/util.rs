@@ -1,64 +1,468 @@
-use reqwest::Client;
+use super::config::HttpConfig;
+use chrono::{DateTime, Utc};
+use reqwest::header::{
+    HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::{Client, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
+use slog::{Drain, Level, LevelFilter, Logger};
+use slog_async::AsyncGuard;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
 use std::io::Read;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 use wx::error::{Error, WxError};
-use wx::util::Logger;
 
-pub struct Fetcher<'a> {
-    pub client: &'a Client,
-    pub logger: &'a Logger,
-    pub user_agent: &'a str,
+// Product documents are around 10KB, so this stays within a few MB
+const DOCUMENT_CACHE_SIZE: usize = 500;
+
+/**
+ * Builds an async terminal logger that drops anything below the configured level. Messages are
+ * written on another thread, so the guard has to be dropped before exiting, which waits for
+ * them to be flushed.
+ */
+pub fn get_logger(app_name: &str, level: Level) -> (Logger, AsyncGuard) {
+    let decorator = slog_term::TermDecorator::new().build();
+    let drain = slog_term::FullFormat::new(decorator).build().fuse();
+    let (drain, guard) = slog_async::Async::new(drain).build_with_guard();
+    let drain = LevelFilter::new(drain.fuse(), level).fuse();
+    let logger = Logger::root(drain, o!("app" => app_name.to_string()));
+    (logger, guard)
 }
 
-impl<'a> Fetcher<'a> {
-    pub fn new(client: &'a Client, logger: &'a Logger, user_agent: &'a str) -> Fetcher<'a> {
-        Fetcher {
-            client,
-            logger,
-            user_agent,
+/**
+ * Why a request failed, and whether it's worth trying again. The API may say how long to wait
+ * before retrying with `Retry-After`.
+ */
+#[derive(Debug, PartialEq)]
+enum FetchError {
+    Retryable(String, Option<Duration>),
+    Permanent(String),
+}
+
+/**
+ * What the API sent back. Conditional requests may be told nothing has changed.
+ */
+enum Body {
+    Modified(String, Validators),
+    NotModified,
+}
+
+/**
+ * Response headers that identify a version of a URL, sent back on the next request so the API
+ * can skip the body if it hasn't changed.
+ */
+#[derive(Clone, Debug, Default)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/**
+ * Recently fetched documents by URL, forgetting the oldest once full.
+ */
+struct DocumentCache {
+    bodies: HashMap<String, String>,
+    order: VecDeque<String>,
+}
+
+impl DocumentCache {
+    fn new() -> DocumentCache {
+        DocumentCache {
+            bodies: HashMap::new(),
+            order: VecDeque::new(),
         }
     }
 
+    fn get(&self, url: &str) -> Option<&String> {
+        self.bodies.get(url)
+    }
+
+    fn insert(&mut self, url: &str, body: String) {
+        if self.bodies.insert(url.to_string(), body).is_some() {
+            return;
+        }
+
+        self.order.push_back(url.to_string());
+        if self.order.len() > DOCUMENT_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.bodies.remove(&oldest);
+            }
+        }
+    }
+}
+
+/**
+ * Spaces out requests to each host, shared by every worker so they don't add up to more than
+ * the API allows. A `Retry-After` from a host pauses everyone's requests to it.
+ */
+struct RateLimiter {
+    interval: Duration,
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            next_request: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /**
+     * Reserves the next slot for a host, returning how long to wait for it.
+     */
+    fn reserve(&self, host: &str) -> Duration {
+        let now = Instant::now();
+        let mut next_request = self.next_request.lock().unwrap();
+        let slot = next_request
+            .get(host)
+            .cloned()
+            .filter(|x| *x > now)
+            .unwrap_or(now);
+        next_request.insert(host.to_string(), slot + self.interval);
+        slot - now
+    }
+
+    fn pause(&self, host: &str, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut next_request = self.next_request.lock().unwrap();
+        let slot = next_request.entry(host.to_string()).or_insert(until);
+        if *slot < until {
+            *slot = until;
+        }
+    }
+}
+
+/**
+ * Fetches JSON from the API. A single fetcher is shared by every worker, since the rate limit
+ * applies across all of them.
+ */
+pub struct Fetcher {
+    client: Client,
+    pub logger: Logger,
+    user_agent: String,
+    max_retries: u32,
+    backoff_base: Duration,
+    backoff_max: Duration,
+    rate_limiter: RateLimiter,
+    validators: Mutex<HashMap<String, Validators>>,
+    documents: Mutex<DocumentCache>,
+}
+
+impl Fetcher {
+    pub fn new(logger: &Logger, user_agent: &str, config: &HttpConfig) -> Result<Fetcher, Error> {
+        let client = Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .timeout(Duration::from_millis(config.request_timeout_ms))
+            .build()
+            .map_err(|e| get_fetch_error(&format!("unable to build http client: {}", e)))?;
+
+        Ok(Fetcher {
+            client,
+            logger: logger.clone(),
+            user_agent: user_agent.to_string(),
+            max_retries: config.max_retries,
+            backoff_base: Duration::from_millis(config.backoff_base_ms),
+            backoff_max: Duration::from_millis(config.backoff_max_ms),
+            rate_limiter: RateLimiter::new(Duration::from_millis(config.min_request_interval_ms)),
+            validators: Mutex::new(HashMap::new()),
+            documents: Mutex::new(DocumentCache::new()),
+        })
+    }
+
+    /**
+     * Fetches and deserializes a URL, retrying anything that might succeed later (network
+     * errors, timeouts, 429s, and 5xx responses) with exponential backoff.
+     */
     pub fn fetch<T: DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
-        let mut response = self
+        parse_body(url, &self.fetch_body(url)?)
+    }
+
+    /**
+     * Like `fetch`, but sends back the `ETag` and `Last-Modified` from the last time the URL
+     * was fetched. Returns None if the API says nothing has changed since then.
+     */
+    pub fn fetch_if_modified<T: DeserializeOwned>(&self, url: &str) -> Result<Option<T>, Error> {
+        let validators = self.validators.lock().unwrap().get(url).cloned();
+
+        match self.get(url, validators.as_ref())? {
+            Body::Modified(body, validators) => {
+                let result = parse_body(url, &body)?;
+                // Only kept once parsed, or a bad response would be skipped until it changes
+                if validators.etag.is_some() || validators.last_modified.is_some() {
+                    self.validators
+                        .lock()
+                        .unwrap()
+                        .insert(url.to_string(), validators);
+                }
+                Ok(Some(result))
+            }
+            Body::NotModified => Ok(None),
+        }
+    }
+
+    /**
+     * Drops what was saved from the last response for a URL, so the next `fetch_if_modified`
+     * gets the whole thing even if it hasn't changed.
+     */
+    pub fn forget_validators(&self, url: &str) {
+        self.validators.lock().unwrap().remove(url);
+    }
+
+    /**
+     * Like `fetch`, for documents that don't change once published (ex. a product by its
+     * `@id`), so recently fetched ones are served from memory.
+     */
+    pub fn fetch_cached<T: DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+        if let Some(body) = self.documents.lock().unwrap().get(url) {
+            debug!(self.logger, "fetch cached"; "url" => url);
+            return parse_body(url, body);
+        }
+
+        let body = self.fetch_body(url)?;
+        let result = parse_body(url, &body)?;
+        self.documents.lock().unwrap().insert(url, body);
+        Ok(result)
+    }
+
+    fn fetch_body(&self, url: &str) -> Result<String, Error> {
+        match self.get(url, None)? {
+            Body::Modified(body, _) => Ok(body),
+            Body::NotModified => Err(get_fetch_error(&format!("not modified: {}", url))),
+        }
+    }
+
+    fn get(&self, url: &str, validators: Option<&Validators>) -> Result<Body, Error> {
+        let host = get_host(url);
+        let mut attempt = 0;
+
+        loop {
+            thread::sleep(self.rate_limiter.reserve(&host));
+
+            let reason = match self.try_get(url, validators) {
+                Ok(value) => return Ok(value),
+                Err(FetchError::Permanent(reason)) => reason,
+                Err(FetchError::Retryable(reason, retry_after)) => {
+                    if attempt < self.max_retries {
+                        let delay = match retry_after {
+                            Some(value) => {
+                                let value = self.clamp_retry_after(url, value);
+                                self.rate_limiter.pause(&host, value);
+                                value
+                            }
+                            None => get_backoff(self.backoff_base, self.backoff_max, attempt),
+                        };
+
+                        attempt += 1;
+                        info!(self.logger, "fetch retry"; "url" => url, "reason" => reason, "attempt" => attempt, "delay_ms" => delay.as_millis() as u64);
+                        thread::sleep(delay);
+                        continue;
+                    }
+                    reason
+                }
+            };
+
+            return Err(get_fetch_error(&reason));
+        }
+    }
+
+    /**
+     * A `Retry-After` pauses every worker's requests to the host, so one that asks for too
+     * long (or a date far in the future) is held to the longest backoff.
+     */
+    fn clamp_retry_after(&self, url: &str, retry_after: Duration) -> Duration {
+        if retry_after <= self.backoff_max {
+            return retry_after;
+        }
+
+        warn!(self.logger, "Clamping Retry-After"; "url" => url, "retry_after_ms" => retry_after.as_millis() as u64, "max_ms" => self.backoff_max.as_millis() as u64);
+        self.backoff_max
+    }
+
+    fn try_get(&self, url: &str, validators: Option<&Validators>) -> Result<Body, FetchError> {
+        let mut request = self
             .client
             .get(url)
-            .header(reqwest::header::USER_AGENT, self.user_agent)
-            .send();
-
-        // Do a single retry if the first call fails
-        if response.is_err() {
-            info!(self.logger, "fetch retry"; "url" => url);
-            response = self
-                .client
-                .get(url)
-                .header(reqwest::header::USER_AGENT, self.user_agent)
-                .send();
-        }
+            .header(USER_AGENT, self.user_agent.as_str());
 
-        if response.is_err() {
-            let msg = format!("unable to fetch url: {}", url);
-            return Err(Error::Wx(<WxError>::new(&msg)));
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                request = request.header(IF_NONE_MATCH, etag.as_str());
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+            }
         }
 
-        let mut response = response.unwrap();
+        let mut response = request.send().map_err(|e| {
+            FetchError::Retryable(format!("unable to fetch url: {}, {}", url, e), None)
+        })?;
+
         let status = response.status();
+        debug!(self.logger, "fetch"; "url" => url, "status" => status.to_string());
+
+        if status == StatusCode::NOT_MODIFIED && validators.is_some() {
+            return Ok(Body::NotModified);
+        }
+
+        if status != StatusCode::OK {
+            let reason = format!("Unexpected status code: {}, url: {}", status, url);
+            let retry_after = get_header(&response, RETRY_AFTER).and_then(|x| get_retry_after(&x));
 
-        if status != reqwest::StatusCode::OK {
-            let msg = format!(
-                "Unexpected status code: {}, url: {}",
-                response.status(),
-                url
-            );
-            return Err(Error::Wx(<WxError>::new(&msg)));
-        } else {
-            debug!(self.logger, "fetch"; "url" => url, "status" => status.to_string());
+            return Err(if is_retryable(status) {
+                FetchError::Retryable(reason, retry_after)
+            } else {
+                FetchError::Permanent(reason)
+            });
         }
 
+        let validators = Validators {
+            etag: get_header(&response, ETAG),
+            last_modified: get_header(&response, LAST_MODIFIED),
+        };
+
         let mut body = String::new();
-        response.read_to_string(&mut body)?;
-        let result: T = serde_json::from_str(&body)?;
+        response.read_to_string(&mut body).map_err(|e| {
+            FetchError::Retryable(format!("unable to read url: {}, {}", url, e), None)
+        })?;
 
-        Ok(result)
+        Ok(Body::Modified(body, validators))
+    }
+}
+
+fn get_header(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|x| x.to_str().ok())
+        .map(ToString::to_string)
+}
+
+fn parse_body<T: DeserializeOwned>(url: &str, body: &str) -> Result<T, Error> {
+    serde_json::from_str(body)
+        .map_err(|e| get_fetch_error(&format!("unable to parse url: {}, {}", url, e)))
+}
+
+fn get_fetch_error(reason: &str) -> Error {
+    Error::Wx(<WxError>::new(reason))
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+/**
+ * `Retry-After` is either a number of seconds or an HTTP date.
+ */
+fn get_retry_after(input: &str) -> Option<Duration> {
+    if let Ok(seconds) = input.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(input.trim()).ok()?;
+    let millis = (date.with_timezone(&Utc) - Utc::now()).num_milliseconds();
+    Some(Duration::from_millis(millis.max(0) as u64))
+}
+
+/**
+ * Doubles the delay with each attempt, up to the max, then picks a random point in its upper
+ * half so workers that failed together don't all retry together.
+ */
+pub fn get_backoff(base: Duration, max: Duration, attempt: u32) -> Duration {
+    let delay = base
+        .checked_mul(2u32.saturating_pow(attempt))
+        .unwrap_or(max)
+        .min(max);
+    delay / 2 + delay.mul_f64(get_jitter() / 2.0)
+}
+
+/**
+ * A random value in [0, 1), without pulling in a dependency just for this.
+ */
+fn get_jitter() -> f64 {
+    let hash = RandomState::new().build_hasher().finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn get_host(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(value) => format!(
+            "{}:{}",
+            value.host_str().unwrap_or(""),
+            value.port_or_known_default().unwrap_or(0)
+        ),
+        Err(_) => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_backoff_should_double_up_to_the_max() {
+        let base = Duration::from_millis(500);
+        let max = Duration::from_millis(3_000);
+        for attempt in 0..10 {
+            let expected = (base * 2u32.pow(attempt.min(3))).min(max);
+            let result = get_backoff(base, max, attempt);
+            assert!(result >= expected / 2 && result <= expected);
+        }
+    }
+
+    #[test]
+    fn get_retry_after_should_read_seconds() {
+        assert_eq!(Some(Duration::from_secs(120)), get_retry_after("120"));
+        assert_eq!(None, get_retry_after("soon"));
+    }
+
+    #[test]
+    fn get_retry_after_past_date_should_not_wait() {
+        let result = get_retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(Some(Duration::from_millis(0)), result);
+    }
+
+    #[test]
+    fn is_retryable_should_classify_statuses() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn rate_limiter_should_space_requests_per_host() {
+        let limiter = RateLimiter::new(Duration::from_secs(1));
+        assert_eq!(Duration::from_secs(0), limiter.reserve("a:443"));
+        assert!(limiter.reserve("a:443") > Duration::from_millis(900));
+        assert_eq!(Duration::from_secs(0), limiter.reserve("b:443"));
+        limiter.pause("b:443", Duration::from_secs(5));
+        assert!(limiter.reserve("b:443") > Duration::from_secs(4));
+    }
+
+    #[test]
+    fn document_cache_should_forget_the_oldest() {
+        let mut cache = DocumentCache::new();
+        for i in 0..=DOCUMENT_CACHE_SIZE {
+            cache.insert(&i.to_string(), i.to_string());
+        }
+        assert_eq!(None, cache.get("0"));
+        assert_eq!(Some(&"1".to_string()), cache.get("1"));
+        assert_eq!(DOCUMENT_CACHE_SIZE, cache.order.len());
+    }
+
+    #[test]
+    fn get_host_should_include_the_port() {
+        assert_eq!(
+            "api.weather.gov:443",
+            get_host("https://api.weather.gov/products")
+        );
+        assert_eq!("127.0.0.1:8080", get_host("http://127.0.0.1:8080/products"));
     }
 }
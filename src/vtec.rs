@@ -0,0 +1,170 @@
+use super::parser::{cap, get_invalid, parse_cap, short_time_to_ticks, FieldError, Regexes};
+use regex::Captures;
+
+// VTEC uses all zeroes for times that aren't applicable, ex. the start of a continued warning
+const EMPTY_TIME: &str = "000000T0000Z";
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ProductClass {
+    Operational,
+    Test,
+    Experimental,
+    ExperimentalVtec,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Action {
+    New,
+    Continued,
+    Extended,
+    ExtendedArea,
+    ExtendedBoth,
+    Upgraded,
+    Cancelled,
+    Expired,
+    Correction,
+    Routine,
+}
+
+/**
+ * A decoded P-VTEC (Primary Valued Time Event Code) string, ex.
+ * `/O.NEW.KMAF.TO.W.0008.190422T2353Z-190423T0045Z/`. Follow-up products reference the
+ * same office, phenomena, significance, and ETN (Event Tracking Number) as the original.
+ */
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Vtec {
+    pub product_class: ProductClass,
+    pub action: Action,
+    pub office: String,
+    pub phenomena: String,
+    pub significance: String,
+    pub etn: u16,
+    pub begin_ts: Option<u64>,
+    pub end_ts: Option<u64>,
+}
+
+impl Vtec {
+    /**
+     * Identifies the hazard across every product issued for it, ex. `KMAF.TO.W.0008`.
+     */
+    pub fn event_id(&self) -> String {
+        format!(
+            "{}.{}.{}.{:04}",
+            self.office, self.phenomena, self.significance, self.etn
+        )
+    }
+}
+
+/**
+ * Returns every VTEC string in the text, in order. Multi-segment products have at least one
+ * per segment.
+ */
+pub fn parse(text: &str, regexes: &Regexes) -> Result<Vec<Vtec>, FieldError> {
+    regexes
+        .vtec
+        .captures_iter(text)
+        .map(|x| from_captures(&x))
+        .collect()
+}
+
+/**
+ * Returns the first VTEC string in the text, which is all single-hazard warnings carry.
+ */
+pub fn parse_first(text: &str, regexes: &Regexes) -> Result<Vtec, FieldError> {
+    let captures = regexes
+        .vtec
+        .captures(text)
+        .ok_or_else(|| FieldError::missing("vtec"))?;
+    from_captures(&captures)
+}
+
+fn from_captures(captures: &Captures) -> Result<Vtec, FieldError> {
+    let raw_class = cap(captures, "class")?;
+    let product_class = match raw_class {
+        "O" => ProductClass::Operational,
+        "T" => ProductClass::Test,
+        "E" => ProductClass::Experimental,
+        "X" => ProductClass::ExperimentalVtec,
+        _ => return Err(get_invalid(captures, "class")),
+    };
+
+    let raw_action = cap(captures, "action")?;
+    let action = match raw_action {
+        "NEW" => Action::New,
+        "CON" => Action::Continued,
+        "EXT" => Action::Extended,
+        "EXA" => Action::ExtendedArea,
+        "EXB" => Action::ExtendedBoth,
+        "UPG" => Action::Upgraded,
+        "CAN" => Action::Cancelled,
+        "EXP" => Action::Expired,
+        "COR" => Action::Correction,
+        "ROU" => Action::Routine,
+        _ => return Err(get_invalid(captures, "action")),
+    };
+
+    Ok(Vtec {
+        product_class,
+        action,
+        office: cap(captures, "office")?.to_string(),
+        phenomena: cap(captures, "phenomena")?.to_string(),
+        significance: cap(captures, "significance")?.to_string(),
+        etn: parse_cap::<u16>(captures, "etn")?,
+        begin_ts: get_ticks(captures, "begin")?,
+        end_ts: get_ticks(captures, "end")?,
+    })
+}
+
+fn get_ticks(captures: &Captures, name: &'static str) -> Result<Option<u64>, FieldError> {
+    let input = cap(captures, name)?;
+    if input == EMPTY_TIME {
+        Ok(None)
+    } else {
+        match short_time_to_ticks(input) {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(get_invalid(captures, name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_first_should_decode_every_field() {
+        let text = "\nTXC135-230045-\n/O.NEW.KMAF.TO.W.0008.190422T2353Z-190423T0045Z/\n";
+        let regexes = Regexes::new();
+        let result = parse_first(text, &regexes).unwrap();
+        let expected = Vtec {
+            product_class: ProductClass::Operational,
+            action: Action::New,
+            office: "KMAF".to_string(),
+            phenomena: "TO".to_string(),
+            significance: "W".to_string(),
+            etn: 8,
+            begin_ts: Some(1555977180000000),
+            end_ts: Some(1555980300000000),
+        };
+        assert_eq!(expected, result);
+        assert_eq!("KMAF.TO.W.0008", result.event_id());
+    }
+
+    #[test]
+    fn parse_should_return_every_segment() {
+        let text = "/O.CAN.KAKQ.TO.W.0036.000000T0000Z-190420T0045Z/\n\n$$\n\n/T.CON.KAKQ.TO.W.0036.000000T0000Z-190420T0045Z/";
+        let regexes = Regexes::new();
+        let result = parse(text, &regexes).unwrap();
+        let actions: Vec<Action> = result.iter().map(|x| x.action).collect();
+        assert_eq!(vec![Action::Cancelled, Action::Continued], actions);
+        assert_eq!(ProductClass::Test, result[1].product_class);
+        assert_eq!(None, result[1].begin_ts);
+    }
+
+    #[test]
+    fn parse_first_without_vtec_should_be_an_error() {
+        let regexes = Regexes::new();
+        let result = parse_first("no vtec here", &regexes);
+        assert!(result.is_err());
+    }
+}
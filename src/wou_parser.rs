@@ -0,0 +1,232 @@
+use super::domain::{NwsEvent, Product, WatchCounties};
+use super::parser::{get_issuance_ticks, FieldError, Regexes};
+use super::ugc;
+use super::vtec::{self, Action};
+use wx::domain::{Event, EventType, Location, Watch, WatchStatus, WatchType};
+
+/**
+ * Parses an SPC Watch Outline Update (WOU) or a WFO Watch County Notification (WCN). Both are
+ * split into UGC segments that each carry a VTEC action and a table of county names, which
+ * get rolled up into a single watch event listing the counties added, continued, extended, or
+ * removed.
+ */
+pub fn parse(product: &Product, regexes: Regexes) -> Result<Option<NwsEvent>, FieldError> {
+    let text = &product.product_text;
+    let event_ts = get_issuance_ticks(product)?;
+    let mut counties = WatchCounties::default();
+    let mut issued_for: Vec<String> = vec![];
+    let mut vtec = None;
+
+    for segment in ugc::get_segments(&text, &regexes) {
+        let segment_vtec =
+            vtec::parse(segment.text, &regexes).map_err(|x| x.within(&text, segment.text))?;
+        let segment_vtec = match segment_vtec.into_iter().next() {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let ugcs = segment.ugcs;
+        match segment_vtec.action {
+            Action::New | Action::ExtendedArea | Action::ExtendedBoth => {
+                counties.added.extend(ugcs)
+            }
+            Action::Extended => counties.extended.extend(ugcs),
+            Action::Cancelled | Action::Expired => counties.removed.extend(ugcs),
+            _ => counties.continued.extend(ugcs),
+        }
+
+        issued_for.extend(get_county_names(segment.text));
+
+        if vtec.is_none() {
+            vtec = Some(segment_vtec);
+        }
+    }
+
+    let vtec = vtec.ok_or_else(|| FieldError::missing("vtec"))?;
+    let id = vtec.etn;
+
+    let (watch_type, watch_type_text) = match vtec.phenomena.as_ref() {
+        "TO" => (WatchType::Tornado, "Tor "),
+        "SV" => (WatchType::SevereThunderstorm, "Tstm "),
+        _ => (WatchType::Other, ""),
+    };
+
+    let status = if !counties.added.is_empty() {
+        WatchStatus::Issued
+    } else if !counties.removed.is_empty()
+        && counties.continued.is_empty()
+        && counties.extended.is_empty()
+    {
+        WatchStatus::Cancelled
+    } else {
+        WatchStatus::Unknown
+    };
+
+    let wfo = product.issuing_office.to_string();
+    let title = format!(
+        "{} {}Watch {} counties: {}",
+        wfo,
+        watch_type_text,
+        id,
+        get_summary(&counties)
+    );
+
+    let issued_for = if issued_for.is_empty() {
+        None
+    } else {
+        Some(issued_for.join("; "))
+    };
+
+    let watch = Watch {
+        is_pds: false,
+        id,
+        issued_for,
+        watch_type,
+        status,
+    };
+
+    let location = Location {
+        wfo: Some(wfo),
+        point: None,
+        poly: None,
+        county: None,
+    };
+
+    let mut event = Event::new(event_ts, EventType::NwsSel, title);
+    event.expires_ts = vtec.end_ts;
+    event.location = Some(location);
+    event.text = Some(text.to_string());
+    event.valid_ts = vtec.begin_ts;
+    event.watch = Some(watch);
+
+    Ok(Some(NwsEvent {
+        vtec: Some(vtec),
+        watch_counties: Some(counties),
+        ..NwsEvent::from(event)
+    }))
+}
+
+fn get_summary(counties: &WatchCounties) -> String {
+    let buckets = vec![
+        (counties.added.len(), "added"),
+        (counties.continued.len(), "continued"),
+        (counties.extended.len(), "extended"),
+        (counties.removed.len(), "removed"),
+    ];
+
+    buckets
+        .iter()
+        .filter(|x| x.0 > 0)
+        .map(|x| format!("{} {}", x.0, x.1))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/**
+ * Reads the county tables out of a segment, grouped by state. WOUs introduce each table with
+ * `.    INDIANA COUNTIES INCLUDED ARE` and WCNs with `IN INDIANA THIS WATCH INCLUDES 10 COUNTIES`.
+ * County names are padded into columns, so anything separated by two or more spaces is a new
+ * name. WCNs may break a state up by region (`IN SOUTH CENTRAL INDIANA`), and end with a list
+ * of cities that isn't part of the table.
+ */
+fn get_county_names(text: &str) -> Vec<String> {
+    let mut states: Vec<(String, Vec<String>)> = vec![];
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with('.') && trimmed.ends_with("INCLUDED ARE") {
+            let state = trimmed
+                .trim_start_matches('.')
+                .trim_end_matches("INCLUDED ARE")
+                .trim()
+                .trim_end_matches(" COUNTIES")
+                .trim_end_matches(" PARISHES");
+            states.push((state.to_string(), vec![]));
+            continue;
+        }
+
+        if trimmed.starts_with("IN ") {
+            if let Some(state) = trimmed.find(" THIS ").and_then(|x| trimmed.get(3..x)) {
+                states.push((state.to_string(), vec![]));
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("THIS INCLUDES") {
+            break;
+        }
+
+        let names = match states.last_mut() {
+            Some(value) => &mut value.1,
+            None => continue,
+        };
+
+        names.extend(
+            trimmed
+                .split("  ")
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(ToString::to_string),
+        );
+    }
+
+    states
+        .into_iter()
+        .filter(|x| !x.1.is_empty())
+        .map(|x| format!("{}: {}", x.0, x.1.join(", ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_util::get_product_from_file;
+    use super::*;
+
+    #[test]
+    fn parse_wou_new_tor_watch() {
+        let product = get_product_from_file("data/products/wou-tor-watch");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap().unwrap();
+        let counties = result.watch_counties.unwrap();
+        let event = result.event;
+        assert_eq!("KWNS Tor Watch 26 counties: 106 added", event.title);
+        assert_eq!(106, counties.added.len());
+        assert_eq!("INC005", counties.added[0]);
+        assert_eq!("OHC165", counties.added[105]);
+        assert_eq!(Some(1522775700000000), event.valid_ts);
+        assert_eq!(Some(1522800000000000), event.expires_ts);
+    }
+
+    #[test]
+    fn parse_wou_tor_watch_update() {
+        let product = get_product_from_file("data/products/wou-tor-watch-update");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap().unwrap();
+        let counties = result.watch_counties.unwrap();
+        let event = result.event;
+        assert_eq!("KWNS Tor Watch 26 counties: 106 continued", event.title);
+        assert!(counties.added.is_empty());
+        assert_eq!(None, event.valid_ts);
+    }
+
+    #[test]
+    fn parse_wcn_tor_watch() {
+        let product = get_product_from_file("data/products/wcn-tor-watch");
+        let regexes = Regexes::new();
+        let result = parse(&product, regexes).unwrap().unwrap();
+        let serialized_result = serde_json::to_string(&result.watch_counties).unwrap();
+        let expected = r#"{"added":["INC019","INC025","INC037","INC043","INC061","INC077","INC117","INC123","INC143","INC175","KYC091","KYC103","KYC111","KYC185","KYC223"],"continued":[],"extended":[],"removed":[]}"#;
+        assert_eq!(expected, serialized_result);
+        let issued_for = result.event.watch.unwrap().issued_for.unwrap();
+        let expected = "INDIANA: CLARK, CRAWFORD, DUBOIS, FLOYD, HARRISON, JEFFERSON, ORANGE, PERRY, SCOTT, WASHINGTON; KENTUCKY: HENRY, JEFFERSON, OLDHAM, TRIMBLE, HANCOCK";
+        assert_eq!(expected, issued_for);
+    }
+
+    #[test]
+    fn get_county_names_should_group_wou_tables_by_state() {
+        let text = "KY \n.    KENTUCKY COUNTIES INCLUDED ARE\n\nBOONE                BRACKEN             CAMPBELL            \nCARROLL              \n";
+        let result = get_county_names(text);
+        assert_eq!(vec!["KENTUCKY: BOONE, BRACKEN, CAMPBELL, CARROLL"], result);
+    }
+}